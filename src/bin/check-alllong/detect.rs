@@ -0,0 +1,183 @@
+use clap::ValueEnum;
+use kovi_plugin_check_alllong::{
+    encode_image, render_report, DetectionReport, OutputFormat, Redaction, RenderOptions,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::{collect_images, read_and_detect, EngineArgs};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// 每行一个 JSON 对象
+    Json,
+    /// 每个检测框一行
+    Csv,
+}
+
+#[derive(clap::Args)]
+pub(crate) struct DetectArgs {
+    /// 图片文件或目录（递归）
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// 输出格式
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// 触发值，仅影响输出中的 violation 字段
+    #[arg(short, long, default_value_t = 0.78)]
+    trigger: f32,
+    /// 保存标注图片的目录，只保存有检测框的图片
+    #[arg(long)]
+    annotate: Option<PathBuf>,
+    /// 标注图片中检测区域的打码方式：none、mosaic、blur
+    #[arg(long, value_parser = parse_lowercase::<Redaction>, default_value = "none")]
+    redaction: Redaction,
+    /// 标注图片格式：png、jpeg、webp
+    #[arg(long, value_parser = parse_lowercase::<OutputFormat>, default_value = "png")]
+    image_format: OutputFormat,
+    /// 不在框上标注类别与置信度
+    #[arg(long)]
+    no_captions: bool,
+}
+
+/// 按配置文件中的写法解析枚举取值
+fn parse_lowercase<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+        .map_err(|_| format!("无效的取值: {}", value))
+}
+
+#[derive(Serialize)]
+struct JsonRow<'a> {
+    path: &'a str,
+    violation: bool,
+    #[serde(flatten)]
+    report: &'a DetectionReport,
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    path: &'a str,
+    detector: &'a str,
+    error: String,
+}
+
+pub(crate) fn run(args: DetectArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let engines = args.engine.load()?;
+    let images = collect_images(&args.paths)?;
+
+    if let Some(dir) = &args.annotate {
+        std::fs::create_dir_all(dir)?;
+    }
+    let render = RenderOptions {
+        captions: !args.no_captions,
+        redaction: args.redaction,
+        output_format: args.image_format,
+        ..RenderOptions::default()
+    };
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    if args.format == Format::Csv {
+        writeln!(
+            out,
+            "path,detector,score,violation,frame_index,label,confidence,x1,y1,x2,y2"
+        )?;
+    }
+
+    for path in &images {
+        let path_str = path.to_string_lossy();
+        for (key, engine) in &engines {
+            let (img_data, report) = match read_and_detect(engine, path) {
+                Ok(v) => v,
+                Err(err) => {
+                    match args.format {
+                        Format::Json => {
+                            let row = JsonError {
+                                path: &path_str,
+                                detector: engine.name(),
+                                error: err.to_string(),
+                            };
+                            writeln!(out, "{}", serde_json::to_string(&row)?)?;
+                        }
+                        Format::Csv => eprintln!("{}: {}", path_str, err),
+                    }
+                    continue;
+                }
+            };
+
+            let violation = report.is_violation(args.trigger);
+            match args.format {
+                Format::Json => {
+                    let row = JsonRow {
+                        path: &path_str,
+                        violation,
+                        report: &report,
+                    };
+                    writeln!(out, "{}", serde_json::to_string(&row)?)?;
+                }
+                Format::Csv => write_csv(&mut out, &path_str, &report, violation)?,
+            }
+
+            if let (Some(dir), false) = (&args.annotate, report.detections.is_empty()) {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let output_path = dir.join(format!(
+                    "{}-{}.{}",
+                    stem,
+                    key,
+                    render.output_format.extension()
+                ));
+                let annotated = render_report(&img_data, &report, &render)?;
+                std::fs::write(output_path, encode_image(&annotated, &render)?)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_csv(
+    out: &mut impl Write,
+    path: &str,
+    report: &DetectionReport,
+    violation: bool,
+) -> std::io::Result<()> {
+    let prefix = format!(
+        "{},{},{:.4},{}",
+        csv_field(path),
+        csv_field(&report.detector),
+        report.score,
+        violation
+    );
+
+    if report.detections.is_empty() {
+        return writeln!(out, "{},,,,,,,", prefix);
+    }
+
+    for d in &report.detections {
+        writeln!(
+            out,
+            "{},{},{},{:.4},{:.1},{:.1},{:.1},{:.1}",
+            prefix,
+            d.frame_index,
+            csv_field(&d.label),
+            d.confidence,
+            d.bbox.x1,
+            d.bbox.y1,
+            d.bbox.x2,
+            d.bbox.y2
+        )?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,425 @@
+use kovi_plugin_check_alllong::Engine;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::{collect_images, read_and_detect, EngineArgs};
+
+#[derive(clap::Args)]
+pub(crate) struct EvalArgs {
+    /// 数据集目录，包含 positives/ 与 negatives/，或 YOLO 格式的 images/ 与 labels/；
+    /// 同时评估多个检测器时正负样本需按检测器放在 long/ 与 nailong/ 子目录中
+    dataset: PathBuf,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// 当前使用的触发值，报告中会与建议值对比
+    #[arg(short, long, default_value_t = 0.78)]
+    trigger: f32,
+    /// 报告输出目录，写入 report.md、curve.csv 与 scores.csv
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// 一张带标注的样本
+struct Sample {
+    path: PathBuf,
+    /// YOLO 标注中的类别，正负样本目录格式下为 `None`
+    classes: Option<Vec<usize>>,
+    positive_dir: bool,
+}
+
+impl Sample {
+    fn is_positive(&self, engine: &Engine) -> bool {
+        match &self.classes {
+            Some(classes) => classes.iter().any(|c| engine.is_violation_class(*c)),
+            None => self.positive_dir,
+        }
+    }
+}
+
+/// 某个阈值下的统计
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    threshold: f32,
+    tp: usize,
+    fp: usize,
+    fn_: usize,
+    tn: usize,
+}
+
+impl Point {
+    fn at(threshold: f32, scored: &[(f32, bool)]) -> Self {
+        let mut point = Point {
+            threshold,
+            tp: 0,
+            fp: 0,
+            fn_: 0,
+            tn: 0,
+        };
+        for &(score, positive) in scored {
+            match (score >= threshold, positive) {
+                (true, true) => point.tp += 1,
+                (true, false) => point.fp += 1,
+                (false, true) => point.fn_ += 1,
+                (false, false) => point.tn += 1,
+            }
+        }
+        point
+    }
+
+    fn precision(&self) -> f32 {
+        ratio(self.tp, self.tp + self.fp, 1.)
+    }
+
+    fn recall(&self) -> f32 {
+        ratio(self.tp, self.tp + self.fn_, 0.)
+    }
+
+    /// 没有负样本时无法计算
+    fn fpr(&self) -> Option<f32> {
+        let negatives = self.fp + self.tn;
+        (negatives > 0).then(|| self.fp as f32 / negatives as f32)
+    }
+
+    fn f1(&self) -> f32 {
+        let (p, r) = (self.precision(), self.recall());
+        if p + r == 0. {
+            0.
+        } else {
+            2. * p * r / (p + r)
+        }
+    }
+}
+
+fn ratio(num: usize, den: usize, empty: f32) -> f32 {
+    if den == 0 {
+        empty
+    } else {
+        num as f32 / den as f32
+    }
+}
+
+/// 以每个不同的分数为阈值，从高到低得到的曲线点
+fn curve(scored: &[(f32, bool)]) -> Vec<Point> {
+    let mut thresholds: Vec<f32> = scored.iter().map(|(score, _)| *score).collect();
+    thresholds.sort_by(|a, b| b.total_cmp(a));
+    thresholds.dedup();
+    thresholds
+        .into_iter()
+        .map(|threshold| Point::at(threshold, scored))
+        .collect()
+}
+
+/// 正负样本都存在时才有意义，否则返回 `None`
+fn roc_auc(curve: &[Point]) -> Option<f32> {
+    let first = curve.first()?;
+    if first.tp + first.fn_ == 0 {
+        return None;
+    }
+    let mut auc = 0.;
+    let (mut last_fpr, mut last_tpr) = (0., 0.);
+    for point in curve {
+        let (fpr, tpr) = (point.fpr()?, point.recall());
+        auc += (fpr - last_fpr) * (tpr + last_tpr) / 2.;
+        (last_fpr, last_tpr) = (fpr, tpr);
+    }
+    Some(auc + (1. - last_fpr) * (1. + last_tpr) / 2.)
+}
+
+fn format_fpr(point: &Point) -> String {
+    point
+        .fpr()
+        .map_or_else(|| "-".to_string(), |v| format!("{:.4}", v))
+}
+
+fn average_precision(curve: &[Point]) -> f32 {
+    let mut ap = 0.;
+    let mut last_recall = 0.;
+    for point in curve {
+        ap += (point.recall() - last_recall) * point.precision();
+        last_recall = point.recall();
+    }
+    ap
+}
+
+/// F1 最高的阈值，F1 相同时取更高的阈值以减少误判
+fn best_threshold(curve: &[Point]) -> Option<Point> {
+    curve
+        .iter()
+        .copied()
+        .reduce(|best, point| if point.f1() > best.f1() { point } else { best })
+}
+
+fn has_split(dir: &Path) -> bool {
+    dir.join("positives").is_dir() || dir.join("negatives").is_dir()
+}
+
+fn load_split(dir: &Path) -> Result<Vec<Sample>, Box<dyn std::error::Error>> {
+    let mut samples = Vec::new();
+    for (name, positive_dir) in [("positives", true), ("negatives", false)] {
+        let dir = dir.join(name);
+        if !dir.is_dir() {
+            continue;
+        }
+        samples.extend(collect_images(&[dir])?.into_iter().map(|path| Sample {
+            path,
+            classes: None,
+            positive_dir,
+        }));
+    }
+    Ok(samples)
+}
+
+fn has_yolo(dir: &Path) -> bool {
+    dir.join("images").is_dir()
+}
+
+/// 读取检测器 `key` 的样本，优先使用 `<key>/` 子目录
+///
+/// 正负样本目录不区分类别，YOLO 标注的类别号也只对应一个模型，所以数据集根目录的样本只能用于单个检测器。
+fn load_samples(
+    dataset: &Path,
+    key: &str,
+    is_single: bool,
+) -> Result<Vec<Sample>, Box<dyn std::error::Error>> {
+    let dir = dataset.join(key);
+    if has_split(&dir) || has_yolo(&dir) {
+        return load_dir(&dir);
+    }
+    if !is_single && (has_split(dataset) || has_yolo(dataset)) {
+        return Err(format!(
+            "数据集根目录的样本只能评估一个检测器，请使用 --detector 指定，或将样本放在 {}/positives、{}/images 等子目录中",
+            key, key
+        )
+        .into());
+    }
+    load_dir(dataset)
+}
+
+fn load_dir(dataset: &Path) -> Result<Vec<Sample>, Box<dyn std::error::Error>> {
+    if has_split(dataset) {
+        return load_split(dataset);
+    }
+
+    let images = dataset.join("images");
+    let labels = dataset.join("labels");
+    if !images.is_dir() {
+        return Err("数据集目录中应包含 positives/ 与 negatives/，或 images/ 与 labels/".into());
+    }
+
+    let mut samples = Vec::new();
+    for path in collect_images(std::slice::from_ref(&images))? {
+        // labels/ 与 images/ 的子目录结构一致，没有标注文件视为负样本
+        let relative = path.strip_prefix(&images)?.with_extension("txt");
+        let classes = match std::fs::read_to_string(labels.join(relative)) {
+            Ok(content) => content
+                .lines()
+                .filter_map(|line| line.split_whitespace().next()?.parse().ok())
+                .collect(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        samples.push(Sample {
+            path,
+            classes: Some(classes),
+            positive_dir: false,
+        });
+    }
+    Ok(samples)
+}
+
+pub(crate) fn run(args: EvalArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let engines = args.engine.load()?;
+    let is_single = engines.len() == 1;
+    let samples = engines
+        .iter()
+        .map(|(key, _)| load_samples(&args.dataset, key, is_single))
+        .collect::<Result<Vec<_>, _>>()?;
+    if samples.iter().any(Vec::is_empty) {
+        return Err("数据集中没有图片".into());
+    }
+
+    let mut report = String::new();
+    let mut curve_csv = String::from("detector,threshold,precision,recall,f1,fpr\n");
+    let mut scores_csv = String::from("detector,path,positive,score\n");
+
+    for ((key, engine), samples) in engines.iter().zip(&samples) {
+        let mut scored = Vec::new();
+        for sample in samples {
+            let positive = sample.is_positive(engine);
+            match read_and_detect(engine, &sample.path) {
+                Ok((_, detection)) => {
+                    scored.push((detection.score, positive));
+                    writeln!(
+                        scores_csv,
+                        "{},{},{},{:.4}",
+                        key,
+                        sample.path.display(),
+                        positive,
+                        detection.score
+                    )?;
+                }
+                Err(err) => eprintln!("{}: {}", sample.path.display(), err),
+            }
+        }
+
+        let curve = curve(&scored);
+        for i in 0..=100 {
+            let point = Point::at(i as f32 / 100., &scored);
+            writeln!(
+                curve_csv,
+                "{},{:.2},{:.4},{:.4},{:.4},{}",
+                key,
+                point.threshold,
+                point.precision(),
+                point.recall(),
+                point.f1(),
+                point
+                    .fpr()
+                    .map_or_else(String::new, |v| format!("{:.4}", v))
+            )?;
+        }
+
+        write_section(&mut report, engine, &scored, &curve, args.trigger)?;
+    }
+
+    print!("{}", report);
+
+    if let Some(dir) = &args.output {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join("report.md"), &report)?;
+        std::fs::write(dir.join("curve.csv"), curve_csv)?;
+        std::fs::write(dir.join("scores.csv"), scores_csv)?;
+    }
+
+    Ok(())
+}
+
+fn write_section(
+    report: &mut String,
+    engine: &Engine,
+    scored: &[(f32, bool)],
+    curve: &[Point],
+    trigger: f32,
+) -> std::fmt::Result {
+    let positives = scored.iter().filter(|(_, positive)| *positive).count();
+
+    writeln!(report, "# {} 评估报告\n", engine.name())?;
+    writeln!(
+        report,
+        "样本 {} 张，正样本 {} 张，负样本 {} 张\n",
+        scored.len(),
+        positives,
+        scored.len() - positives
+    )?;
+    match roc_auc(curve) {
+        Some(auc) => writeln!(report, "- ROC AUC: {:.4}", auc)?,
+        None => {
+            let missing = if positives == 0 {
+                "正样本"
+            } else {
+                "负样本"
+            };
+            eprintln!(
+                "{}: 缺少{}，无法计算 ROC AUC 与 FPR",
+                engine.name(),
+                missing
+            );
+            writeln!(report, "- ROC AUC: 缺少{}，无法计算", missing)?;
+        }
+    }
+    writeln!(report, "- PR AP: {:.4}", average_precision(curve))?;
+
+    let current = Point::at(trigger, scored);
+    writeln!(
+        report,
+        "- 当前 trigger {:.2}: precision {:.4}, recall {:.4}, F1 {:.4}",
+        trigger,
+        current.precision(),
+        current.recall(),
+        current.f1()
+    )?;
+    if let Some(best) = best_threshold(curve) {
+        writeln!(
+            report,
+            "- 建议 trigger {:.3}: precision {:.4}, recall {:.4}, F1 {:.4}",
+            best.threshold,
+            best.precision(),
+            best.recall(),
+            best.f1()
+        )?;
+    }
+
+    writeln!(
+        report,
+        "\n| trigger | precision | recall | F1 | FPR | TP | FP | FN | TN |"
+    )?;
+    writeln!(report, "|---|---|---|---|---|---|---|---|---|")?;
+    for i in 1..20 {
+        let point = Point::at(i as f32 * 0.05, scored);
+        writeln!(
+            report,
+            "| {:.2} | {:.4} | {:.4} | {:.4} | {} | {} | {} | {} | {} |",
+            point.threshold,
+            point.precision(),
+            point.recall(),
+            point.f1(),
+            format_fpr(&point),
+            point.tp,
+            point.fp,
+            point.fn_,
+            point.tn
+        )?;
+    }
+    writeln!(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_counts() {
+        let scored = [(0.9, true), (0.8, false), (0.7, true), (0.1, false)];
+        let point = Point::at(0.75, &scored);
+        assert_eq!((point.tp, point.fp, point.fn_, point.tn), (1, 1, 1, 1));
+        assert_eq!(point.precision(), 0.5);
+        assert_eq!(point.recall(), 0.5);
+        assert_eq!(point.fpr(), Some(0.5));
+    }
+
+    #[test]
+    fn roc_auc_of_scores() {
+        let scored = [(0.9, true), (0.8, false), (0.7, true), (0.1, false)];
+        assert_eq!(roc_auc(&curve(&scored)), Some(0.75));
+
+        let separated = [(0.9, true), (0.8, true), (0.2, false)];
+        assert_eq!(roc_auc(&curve(&separated)), Some(1.));
+    }
+
+    #[test]
+    fn roc_auc_needs_both_classes() {
+        let positives = [(0.9, true), (0.2, true)];
+        assert_eq!(Point::at(0.5, &positives).fpr(), None);
+        assert_eq!(roc_auc(&curve(&positives)), None);
+
+        let negatives = [(0.9, false), (0.2, false)];
+        assert_eq!(roc_auc(&curve(&negatives)), None);
+        assert_eq!(roc_auc(&[]), None);
+    }
+
+    #[test]
+    fn root_dataset_only_for_single_detector() {
+        let dataset =
+            std::env::temp_dir().join(format!("check-alllong-eval-{}", std::process::id()));
+        std::fs::create_dir_all(dataset.join("images")).unwrap();
+
+        assert!(load_samples(&dataset, "long", false).is_err());
+        assert!(load_samples(&dataset, "long", true).unwrap().is_empty());
+
+        std::fs::create_dir_all(dataset.join("long").join("positives")).unwrap();
+        assert!(load_samples(&dataset, "long", false).unwrap().is_empty());
+        assert!(load_samples(&dataset, "nailong", false).is_err());
+
+        std::fs::remove_dir_all(&dataset).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use kovi_plugin_check_alllong::{DetectionReport, Engine, EngineOptions};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod detect;
+mod eval;
+
+#[derive(Parser)]
+#[command(name = "check-alllong", about = "离线运行龙图与奶龙检测模型")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 检测图片文件或目录，逐张输出结果
+    Detect(detect::DetectArgs),
+    /// 在标注数据集上评估检测器，给出 precision/recall 与建议的 trigger
+    Eval(eval::EvalArgs),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Which {
+    All,
+    Long,
+    Nailong,
+}
+
+/// 模型选择与配置，各子命令共用
+#[derive(clap::Args)]
+struct EngineArgs {
+    /// 使用的检测器
+    #[arg(short, long, value_enum, default_value_t = Which::All)]
+    detector: Which,
+    /// 龙图配置文件，可直接使用插件的 long_config.json
+    #[arg(long)]
+    long_config: Option<PathBuf>,
+    /// 奶龙配置文件，可直接使用插件的 nailong_config.json
+    #[arg(long)]
+    nailong_config: Option<PathBuf>,
+}
+
+impl EngineArgs {
+    /// 按参数创建引擎，返回 `(简称, 引擎)`
+    fn load(&self) -> Result<Vec<(&'static str, Engine)>, Box<dyn std::error::Error>> {
+        let mut engines = Vec::new();
+        if self.detector != Which::Nailong {
+            let options = load_options(self.long_config.as_deref(), EngineOptions::long())?;
+            engines.push(("long", Engine::long(options)?));
+        }
+        if self.detector != Which::Long {
+            let options = load_options(self.nailong_config.as_deref(), EngineOptions::nailong())?;
+            engines.push(("nailong", Engine::nailong(options)?));
+        }
+        Ok(engines)
+    }
+}
+
+fn load_options(
+    path: Option<&Path>,
+    default: EngineOptions,
+) -> Result<EngineOptions, Box<dyn std::error::Error>> {
+    let Some(path) = path else {
+        return Ok(default);
+    };
+    // 与插件一致，旧配置文件使用默认类别
+    let mut options: EngineOptions = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    options.migrate_labels(&default);
+    Ok(options)
+}
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+/// 展开目录，收集其中的图片文件
+fn collect_images(paths: &[PathBuf]) -> std::io::Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            entries.sort();
+
+            let (dirs, files): (Vec<_>, Vec<_>) = entries.into_iter().partition(|p| p.is_dir());
+            images.extend(files.into_iter().filter(|p| is_image(p)));
+            images.extend(collect_images(&dirs)?);
+        } else {
+            images.push(path.clone());
+        }
+    }
+    Ok(images)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// 读取图片并检测，返回原始字节以便后续绘制
+fn read_and_detect(
+    engine: &Engine,
+    path: &Path,
+) -> Result<(Vec<u8>, DetectionReport), Box<dyn std::error::Error>> {
+    let img_data = std::fs::read(path).map_err(|err| format!("读取文件失败: {}", err))?;
+    let report = engine.detect_blocking(&img_data)?;
+    Ok((img_data, report))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let res = match cli.command {
+        Command::Detect(args) => detect::run(args),
+        Command::Eval(args) => eval::run(args),
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("错误: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
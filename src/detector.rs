@@ -1,80 +1,264 @@
-use image::codecs::gif::GifDecoder;
-use image::AnimationDecoder;
-use image::{imageops::FilterType, GenericImageView};
-use image::{DynamicImage, ImageFormat};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use image::{DynamicImage, RgbaImage};
+use kovi::chrono::{Local, NaiveDate};
 use kovi::log::{error, info};
-use kovi::{chrono, tokio, AllMsgEvent, Message, RuntimeBot};
-use ndarray::{s, Array, Axis};
-use ort::inputs;
-use ort::session::builder::GraphOptimizationLevel;
-use ort::session::{Session, SessionOutputs};
-use raqote::{DrawOptions, DrawTarget, LineJoin, PathBuilder, SolidSource, Source, StrokeStyle};
+use kovi::{tokio, AllMsgEvent, Message, RuntimeBot};
 use std::collections::HashMap;
-use std::io::Cursor;
-use std::ops::Deref;
-use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::{Config, UserInfo};
+use crate::engine::{decode_frames_async, DetectError, Engine};
+use crate::extract::FetchedImage;
+use crate::locale::Messages;
+use crate::notify::Notifier;
+use crate::permission::Permissions;
+use crate::postprocess::BoundingBox;
+use crate::ratelimit::RateLimiter;
+use crate::render::{best_frame, draw_report, encode_image, redact, redact_report, Redaction};
+use crate::template::Vars;
+use crate::tmp::{TmpFile, TmpManager};
+use crate::{Config, DetectionReport, UserInfo};
+
+/// 单张图片的检测结果
+struct ImageResult<'a> {
+    /// 从 1 开始的图片序号
+    index: usize,
+    img: &'a FetchedImage,
+    result: Result<(DetectionReport, Vec<DynamicImage>), DetectError>,
+}
+
+/// 违规图片打码后的帧，多个检测器命中同一张图片时合并打码区域
+struct Redacted {
+    /// 图片所在的消息段
+    segment_index: usize,
+    /// 合并转发内的图片无法重建，为 `None`
+    image: Option<RedactedImage>,
+}
+
+struct RedactedImage {
+    score: f32,
+    img: RgbaImage,
+    boxes: Vec<BoundingBox>,
+    redaction: Redaction,
+}
+
+impl RedactedImage {
+    /// 与检测模式的标注图片相同，只打码置信度最高的一帧
+    fn new(report: &DetectionReport, frames: &[DynamicImage], redaction: Redaction) -> Self {
+        Self {
+            score: report.score,
+            img: redact_report(report, frames, redaction),
+            boxes: best_frame(report).1.iter().map(|d| d.bbox).collect(),
+            redaction,
+        }
+    }
+}
+
+/// 相似度达到 `trigger` 的图片，检测模式与执法模式共用
+fn violations<'a, 'b>(
+    results: &'b [ImageResult<'a>],
+    trigger: f32,
+) -> Vec<(&'b ImageResult<'a>, &'b DetectionReport, &'b [DynamicImage])> {
+    results
+        .iter()
+        .filter_map(|v| match &v.result {
+            Ok((report, frames)) if report.is_violation(trigger) => Some((v, report, &frames[..])),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 单个检测器对一条消息的处理结果，回复、禁言、撤回与重发由 [`process_images`] 合并执行
+struct Outcome<'a> {
+    detector: &'a Detector,
+    reply: Option<Message>,
+    /// 禁言时长与提示
+    ban: Option<(usize, Message)>,
+    is_delete: bool,
+    is_repost: bool,
+    redacted: Vec<Redacted>,
+    tmp_files: Vec<TmpFile>,
+}
+
+/// 依次交给本群开启的检测器处理
+///
+/// 一条消息只回复、禁言、撤回与重发一次：回复合并各检测器的逐张说明，禁言取最长的时长，
+/// 重发时合并所有检测器的打码区域，避免重发其他检测器命中的图片。
+pub(crate) async fn process_images(
+    detectors: &[&Detector],
+    e: &AllMsgEvent,
+    bot: &RuntimeBot,
+    imgs_data: &[FetchedImage],
+    is_check_mode: bool,
+) {
+    let is_repost = !is_check_mode && detectors.iter().any(|v| v.config.is_repost_redacted);
+    let mut outcomes = Vec::new();
+    for detector in detectors {
+        let outcome = if is_check_mode {
+            detector.check(e, imgs_data).await
+        } else {
+            detector.enforce(e, bot, imgs_data, is_repost).await
+        };
+        outcomes.extend(outcome);
+    }
+    if outcomes.is_empty() {
+        return;
+    }
+
+    // 命中的检测器中有一个需要重发就重发，否则有一个需要撤回就撤回
+    let reposter = outcomes.iter().find(|v| v.is_repost).map(|v| v.detector);
+    let is_delete = outcomes.iter().any(|v| v.is_delete);
+    let mut ban: Option<(usize, Message)> = None;
+    let mut replies = Vec::new();
+    let mut redacted = Vec::new();
+    let mut tmp_files = Vec::new();
+    for outcome in outcomes {
+        if let Some((duration, msg)) = outcome.ban {
+            if ban.as_ref().is_none_or(|(longest, _)| duration > *longest) {
+                ban = Some((duration, msg));
+            }
+        }
+        replies.extend(outcome.reply);
+        redacted.extend(outcome.redacted);
+        tmp_files.extend(outcome.tmp_files);
+    }
+    if let Some((duration, msg)) = ban {
+        bot.set_group_ban(e.group_id.unwrap(), e.user_id, duration);
+        replies.insert(0, msg);
+    }
+    let reply = join_messages(replies);
+
+    if let Some(detector) = reposter {
+        detector
+            .repost_redacted(e, bot, reply, merge_redacted(redacted))
+            .await;
+    } else {
+        if let Some(msg) = reply {
+            e.reply_and_quote(msg);
+            if is_delete {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+        if is_delete {
+            bot.delete_msg(e.message_id);
+        }
+    }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) struct BoundingBox {
-    pub(crate) x1: f32,
-    pub(crate) y1: f32,
-    pub(crate) x2: f32,
-    pub(crate) y2: f32,
+    TmpFile::release_later(tmp_files);
+}
+
+/// 把多个检测器的回复拼成一条，各自之间换行
+fn join_messages(msgs: Vec<Message>) -> Option<Message> {
+    let mut msgs = msgs.into_iter();
+    let mut joined = msgs.next()?;
+    for msg in msgs {
+        joined.push_text("\n");
+        for segment in msg.iter() {
+            joined.push(segment.clone());
+        }
+    }
+    Some(joined)
+}
+
+/// 合并各检测器的打码结果：同一张图片使用相似度最高的检测器打码后的帧，再叠加其他检测器的打码区域
+fn merge_redacted(mut redacted: Vec<Redacted>) -> Vec<(usize, Option<RgbaImage>)> {
+    let score = |v: &Redacted| v.image.as_ref().map_or(f32::INFINITY, |v| v.score);
+    redacted.sort_by(|a, b| {
+        a.segment_index
+            .cmp(&b.segment_index)
+            .then_with(|| score(b).total_cmp(&score(a)))
+    });
+
+    let mut merged: Vec<(usize, Option<RgbaImage>)> = Vec::new();
+    for item in redacted {
+        match merged.last_mut() {
+            Some((index, img)) if *index == item.segment_index => {
+                let Some(other) = item.image else {
+                    *img = None;
+                    continue;
+                };
+                if let Some(img) = img {
+                    for bbox in &other.boxes {
+                        redact(img, bbox, other.redaction);
+                    }
+                }
+            }
+            _ => merged.push((item.segment_index, item.image.map(|v| v.img))),
+        }
+    }
+    merged
 }
 
 #[derive(Clone)]
 pub(crate) struct Detector {
-    pub(crate) model: Arc<Session>,
+    pub(crate) engine: Engine,
     pub(crate) config: Arc<Config>,
-    pub(crate) labels: Arc<Vec<&'static str>>,
     pub(crate) whitelist: Arc<RwLock<HashMap<i64, bool>>>,
+    /// 观察模式的群，只记录不处理
+    pub(crate) observe: Arc<RwLock<HashMap<i64, bool>>>,
     pub(crate) user_info: Arc<Mutex<HashMap<i64, UserInfo>>>,
-    pub(crate) data_path: Arc<PathBuf>,
+    /// 观察模式下的次数，与正式次数分开统计
+    pub(crate) observe_user_info: Arc<Mutex<HashMap<i64, UserInfo>>>,
+    pub(crate) tmp: TmpManager,
+    pub(crate) notifier: Notifier,
+    pub(crate) limiter: RateLimiter,
+    pub(crate) messages: Messages,
+    /// 各群今日的检测次数，重启后重新计数
+    pub(crate) today: Arc<Mutex<HashMap<i64, (NaiveDate, u64)>>>,
     pub(crate) name: Arc<String>,
 }
 
 impl Detector {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         model_bytes: &[u8],
         config: Config,
-        labels: Vec<&'static str>,
+        fallback_labels: Vec<&'static str>,
         whitelist: Arc<RwLock<HashMap<i64, bool>>>,
+        observe: Arc<RwLock<HashMap<i64, bool>>>,
         user_info: Arc<Mutex<HashMap<i64, UserInfo>>>,
-        data_path: PathBuf,
+        observe_user_info: Arc<Mutex<HashMap<i64, UserInfo>>>,
+        tmp: TmpManager,
+        notifier: Notifier,
+        limiter: RateLimiter,
+        messages: Messages,
         name: String,
     ) -> Self {
-        let model = Session::builder()
-            .unwrap()
-            .with_optimization_level(GraphOptimizationLevel::Level3)
-            .unwrap()
-            .with_intra_threads(4)
-            .unwrap()
-            .commit_from_memory(model_bytes)
-            .unwrap();
+        let engine =
+            Engine::new(&name, model_bytes, &fallback_labels, config.engine.clone()).unwrap();
 
         Self {
-            model: Arc::new(model),
+            engine,
             config: Arc::new(config),
-            labels: Arc::new(labels),
             whitelist,
+            observe,
             user_info,
-            data_path: Arc::new(data_path),
+            observe_user_info,
+            tmp,
+            notifier,
+            limiter,
+            messages,
+            today: Arc::new(Mutex::new(HashMap::new())),
             name: Arc::new(name),
         }
     }
 
-    pub(crate) fn handle_admin_command(&self, e: &AllMsgEvent) {
+    pub(crate) async fn handle_admin_command(
+        &self,
+        e: &AllMsgEvent,
+        bot: &RuntimeBot,
+        permissions: &Permissions,
+    ) {
         if e.text.is_none() {
             return;
         }
 
         let text = e.borrow_text().unwrap();
-        if text != self.config.start_cmd && text != self.config.stop_cmd {
+        if text != self.config.start_cmd
+            && text != self.config.stop_cmd
+            && text != self.config.observe_cmd
+        {
             return;
         }
 
@@ -82,16 +266,166 @@ impl Detector {
             return;
         }
 
+        if !permissions.check(bot, e).await {
+            return;
+        }
+
         let mut whitelist = self.whitelist.write().unwrap();
+        let mut observe = self.observe.write().unwrap();
         let group_id = e.group_id.unwrap();
 
+        // 戒严与观察互斥
+        let vars = self.vars(group_id).sender(e);
         if text == self.config.start_cmd {
             whitelist.insert(group_id, true);
-            e.reply(&self.config.start_msg);
+            observe.insert(group_id, false);
+            e.reply(self.template_message(group_id, "start_msg", &vars));
         } else if text == self.config.stop_cmd {
             whitelist.insert(group_id, false);
-            e.reply(&self.config.stop_msg);
+            observe.insert(group_id, false);
+            e.reply(self.template_message(group_id, "stop_msg", &vars));
+        } else if text == self.config.observe_cmd {
+            whitelist.insert(group_id, false);
+            observe.insert(group_id, true);
+            e.reply(self.template_message(group_id, "observe_msg", &vars));
+        }
+    }
+
+    /// 按 `schedule` 定时开启、关闭戒严
+    ///
+    /// 只在进入或离开戒严时间时切换，期间的手动命令仍然有效；启动时按时间表同步一次。
+    pub(crate) fn spawn_schedule(&self, bot: Arc<RuntimeBot>) -> tokio::task::JoinHandle<()> {
+        let detector = self.clone();
+        tokio::spawn(async move {
+            let schedule = &detector.config.schedule;
+            let interval = Duration::from_secs(schedule.check_interval_secs.max(1));
+            let mut last = HashMap::new();
+            loop {
+                for (group_id, active) in schedule.active_groups() {
+                    if last.insert(group_id, active) == Some(active) {
+                        continue;
+                    }
+                    if !detector.set_enforced(group_id, active) || !schedule.is_announce {
+                        continue;
+                    }
+                    info!(
+                        "{} 定时{}戒严: 群 {}",
+                        detector.name,
+                        if active { "开启" } else { "关闭" },
+                        group_id
+                    );
+                    let key = if active { "start_msg" } else { "stop_msg" };
+                    let vars = detector.vars(group_id);
+                    bot.send_group_msg(group_id, detector.template_message(group_id, key, &vars));
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// 开启或关闭戒严，返回状态是否改变
+    fn set_enforced(&self, group_id: i64, enforced: bool) -> bool {
+        let mut whitelist = self.whitelist.write().unwrap();
+        let mut observe = self.observe.write().unwrap();
+        let previous = whitelist.insert(group_id, enforced).unwrap_or(false);
+        if enforced {
+            observe.insert(group_id, false);
         }
+        previous != enforced
+    }
+
+    /// 检测器相关的模板变量：`{detector}`
+    fn vars(&self, group_id: i64) -> Vars {
+        Vars::new().set(
+            "detector",
+            self.messages.detector_name(group_id, &self.name),
+        )
+    }
+
+    /// 检测器配置中可自定义的消息，见 [`Messages::configured`]
+    fn template_message(&self, group_id: i64, key: &str, vars: &Vars) -> Message {
+        let configured = match key {
+            "reply_msg" => &self.config.reply_msg,
+            "ban_msg" => &self.config.ban_msg,
+            "start_msg" => &self.config.start_msg,
+            "stop_msg" => &self.config.stop_msg,
+            "observe_msg" => &self.config.observe_msg,
+            "repost_msg" => &self.config.repost_msg,
+            _ => return self.messages.message(group_id, key, vars),
+        };
+        self.messages
+            .configured(group_id, key, configured)
+            .message(vars)
+    }
+
+    /// 本群是否开启了戒严或观察模式
+    pub(crate) fn is_enabled(&self, group_id: i64) -> bool {
+        *self
+            .whitelist
+            .read()
+            .unwrap()
+            .get(&group_id)
+            .unwrap_or(&false)
+            || self.is_observing(group_id)
+    }
+
+    pub(crate) fn is_observing(&self, group_id: i64) -> bool {
+        *self
+            .observe
+            .read()
+            .unwrap()
+            .get(&group_id)
+            .unwrap_or(&false)
+    }
+
+    /// 本群的状态说明，由 `.lostatus` 汇总两个检测器
+    pub(crate) fn status(&self, group_id: i64) -> String {
+        let state = if self.is_observing(group_id) {
+            "state_observing"
+        } else if self.is_enabled(group_id) {
+            "state_enforced"
+        } else {
+            "state_disabled"
+        };
+
+        let options = self.engine.options();
+        let labels: Vec<_> = self
+            .engine
+            .labels()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.engine.is_violation_class(*i))
+            .map(|(_, label)| label.as_str())
+            .collect();
+
+        let today = Local::now().date_naive();
+        let today_times = match self.today.lock().unwrap().get(&group_id) {
+            Some((date, times)) if *date == today => *times,
+            _ => 0,
+        };
+
+        let separator = self.messages.text(group_id, "separator", &Vars::new());
+        let vars = self
+            .vars(group_id)
+            .ban(self.config.ban_duration)
+            .set("state", self.messages.text(group_id, state, &Vars::new()))
+            .set("trigger", format!("{:.2}", self.config.trigger))
+            .set("conf", format!("{:.2}", options.postprocess.conf_threshold))
+            .set("labels", labels.join(&separator))
+            .set("ban_cooldown", self.config.ban_cooldown)
+            .set("today", today_times)
+            .set("version", self.engine.version());
+        self.messages.text(group_id, "status", &vars)
+    }
+
+    fn record_today(&self, group_id: i64) {
+        let today = Local::now().date_naive();
+        let mut counts = self.today.lock().unwrap();
+        let entry = counts.entry(group_id).or_insert((today, 0));
+        if entry.0 != today {
+            *entry = (today, 0);
+        }
+        entry.1 += 1;
     }
 
     pub(crate) fn handle_my_times(&self, e: &AllMsgEvent) {
@@ -109,177 +443,260 @@ impl Detector {
         }
 
         let group_id = e.group_id.unwrap();
-        let user_id = e.user_id;
+        let vars = self.vars(group_id).sender(e);
+        match self.times(group_id, e.user_id) {
+            Some((group_times, total_times)) => {
+                let vars = vars.set("count", group_times).set("total", total_times);
+                e.reply(self.messages.message(group_id, "my_times", &vars));
+            }
+            None => e.reply(self.messages.message(group_id, "my_times_none", &vars)),
+        }
+    }
+
+    /// 成员在本群与所有群的发送次数
+    fn times(&self, group_id: i64, user_id: i64) -> Option<(u64, u64)> {
         let user_info_lock = self.user_info.lock().unwrap();
+        let user_data = user_info_lock.get(&user_id)?;
+        let group_times = *user_data.group_total_times.get(&group_id).unwrap_or(&0);
+        Some((group_times, user_data.total_times))
+    }
 
-        if let Some(user_data) = user_info_lock.get(&user_id) {
-            let group_times = user_data.group_total_times.get(&group_id).unwrap_or(&0);
-            let total_times = user_data.total_times;
-            let reply_msg = format!(
-                "你在本群发送{}的次数为: {}\n你的总发送次数为: {}",
-                self.name, group_times, total_times
-            );
-            e.reply(&reply_msg);
-        } else {
-            e.reply(&format!("你还没有发送过{}哦~", self.name));
+    /// 依次检测所有图片，单张失败不影响其余图片
+    async fn detect_all<'a>(
+        &self,
+        group_id: i64,
+        imgs_data: &'a [FetchedImage],
+    ) -> Vec<ImageResult<'a>> {
+        let mut results = Vec::with_capacity(imgs_data.len());
+        for (i, img) in imgs_data.iter().enumerate() {
+            let result = match decode_frames_async(&img.data, self.config.engine.gif_frames).await {
+                Ok(frames) => self.engine.detect_frames(group_id, frames).await,
+                Err(err) => Err(err),
+            };
+            match &result {
+                Ok((report, _)) => info!("{} 第 {} 张 prob: {}", self.name, i + 1, report.score),
+                Err(err) => error!("{} 第 {} 张检测失败: {}", self.name, i + 1, err),
+            }
+            results.push(ImageResult {
+                index: i + 1,
+                img,
+                result,
+            });
         }
+        results
     }
 
-    pub(crate) async fn process_images(
-        &self,
-        e: &AllMsgEvent,
-        bot: &RuntimeBot,
-        imgs_data: &Vec<(Vec<u8>, ImageFormat)>,
-        is_check_mode: bool,
-    ) {
-        if is_check_mode {
-            self.send_with_img(e, bot, &imgs_data).await;
-        } else {
-            self.send_not_img(e, bot, &imgs_data).await;
+    /// 回复开头，多张图片时附上违规张数
+    fn reply_header(&self, group_id: i64, vars: &Vars, total: usize, violations: usize) -> Message {
+        let mut msg = self.template_message(group_id, "reply_msg", vars);
+        if total > 1 {
+            let vars = Vars::new()
+                .set("total", total)
+                .set("violations", violations);
+            msg.push_text(format!(
+                "\n{}",
+                self.messages.text(group_id, "reply_summary", &vars)
+            ));
         }
+        msg
     }
 
-    pub(crate) async fn send_with_img(
+    /// 单张违规图片的说明：序号、类别、相似度、帧与位置
+    fn describe_image(
         &self,
-        e: &AllMsgEvent,
-        bot: &RuntimeBot,
-        imgs_data: &Vec<(Vec<u8>, ImageFormat)>,
-    ) {
-        let mut msg = Message::from(&self.config.reply_msg);
-        let mut detected = false;
-        let mut remove_img_path = Vec::new();
-
-        let mut i = 0;
-        for (img_data, img_type) in imgs_data {
-            i += 1;
-            let (res_img, prob) = match img_type {
-                ImageFormat::Gif => {
-                    let img = extract_frame_from_gif_bytes(&img_data, 0).await.unwrap();
-                    match self.process_image_with_image(img) {
-                        Ok(v) => v,
-                        Err(err) => {
-                            error!("{}", err);
-                            return;
-                        }
-                    }
-                }
-                _ => {
-                    let original_img = image::load_from_memory(&img_data).unwrap();
-                    match self.process_image_with_image(original_img) {
-                        Ok(v) => v,
-                        Err(err) => {
-                            error!("{}", err);
-                            return;
-                        }
-                    }
-                }
-            };
+        group_id: i64,
+        index: usize,
+        img: &FetchedImage,
+        report: &DetectionReport,
+    ) -> String {
+        let text = |key, vars: Vars| self.messages.text(group_id, key, &vars);
+
+        let mut line = format!("\n{}", text("image_index", Vars::new().set("index", index)));
+        let best = report
+            .detections
+            .iter()
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence));
+        if let Some(best) = best {
+            line.push_str(&text("image_label", Vars::new().set("label", &best.label)));
+            if report.frames > 1 {
+                line.push_str(&text(
+                    "image_frame",
+                    Vars::new().set("frame", best.frame_index + 1),
+                ));
+            }
+        }
+        if self.config.is_reply_trigger {
+            line.push_str(&text(
+                "image_score",
+                Vars::new().set("prob", format!("{:.2}", report.score)),
+            ));
+        }
+        if let Some(node) = &img.node {
+            line.push_str(&format!(
+                "\n{}",
+                text("image_node", Vars::new().set("node", node))
+            ));
+        }
+        line
+    }
 
-            info!("{} prob: {}", self.name, prob);
+    /// 回复中使用的发送者与相似度变量
+    fn reply_vars(&self, e: &AllMsgEvent, scores: &[f32]) -> Vars {
+        let group_id = e.group_id.unwrap();
+        let prob = scores.iter().copied().fold(0.0, f32::max);
+        let (count, total) = self.times(group_id, e.user_id).unwrap_or_default();
+        self.vars(group_id)
+            .sender(e)
+            .set("prob", format!("{:.2}", prob))
+            .set("count", count)
+            .set("total", total)
+    }
 
-            if prob >= self.config.trigger {
-                detected = true;
-                let filename = format!(
-                    "{}-{}-output.png",
-                    chrono::Local::now().format("%Y-%m-%d-%H-%M-%S"),
-                    i
-                );
-                let output_path = self.data_path.join("tmp").join(filename);
+    /// 检测模式：回复逐张说明与标注图片，不禁言
+    async fn check(&self, e: &AllMsgEvent, imgs_data: &[FetchedImage]) -> Option<Outcome<'_>> {
+        let group_id = e.group_id.unwrap();
+        let results = self.detect_all(group_id, imgs_data).await;
 
-                if let Some(parent_dir) = output_path.parent() {
-                    if !parent_dir.exists() {
-                        tokio::fs::create_dir_all(parent_dir).await.unwrap();
-                    }
-                }
+        let violations = violations(&results, self.config.trigger);
+        let failed: Vec<_> = results.iter().filter(|v| v.result.is_err()).collect();
+        if violations.is_empty() && failed.is_empty() {
+            return None;
+        }
 
-                image::save_buffer(
-                    &output_path,
-                    &res_img,
-                    res_img.width(),
-                    res_img.height(),
-                    image::ColorType::Rgba8,
-                )
-                .unwrap();
-
-                if self.config.is_reply_trigger {
-                    msg.push_text(format!("\n相似度：{:.2}", prob));
+        let scores: Vec<_> = violations
+            .iter()
+            .map(|(_, report, _)| report.score)
+            .collect();
+        let vars = self.reply_vars(e, &scores);
+        let mut msg = if violations.is_empty() {
+            self.messages.message(group_id, "detect_failed", &vars)
+        } else {
+            self.reply_header(group_id, &vars, results.len(), violations.len())
+        };
+        let render_failed = format!(
+            "\n{}",
+            self.messages
+                .text(group_id, "image_render_failed", &Vars::new())
+        );
+        let mut tmp_files = Vec::new();
+        for (result, report, frames) in violations.iter().copied() {
+            msg.push_text(self.describe_image(group_id, result.index, result.img, report));
+
+            let res_img = draw_report(report, frames, &self.config.render);
+            let file = match encode_image(&res_img, &self.config.render) {
+                Ok(res_data) => {
+                    self.image_file(
+                        res_data,
+                        &format!("{}-output", result.index),
+                        &mut tmp_files,
+                    )
+                    .await
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    msg.push_text(&render_failed);
+                    continue;
+                }
+            };
+            match file {
+                Ok(file) => msg.push_image(&file),
+                Err(err) => {
+                    error!("写入临时文件失败: {}", err);
+                    msg.push_text(&render_failed);
                 }
-                msg.push_image(output_path.to_str().unwrap());
-
-                remove_img_path.push(output_path);
             }
         }
-
-        if !detected {
-            delete(&remove_img_path).await;
-            return;
-        }
-
-        e.reply_and_quote(msg);
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        if self.config.is_delete_message {
-            bot.delete_msg(e.message_id);
+        for result in failed {
+            if let Err(err) = &result.result {
+                let vars = Vars::new().set("index", result.index).set("error", err);
+                msg.push_text(format!(
+                    "\n{}",
+                    self.messages.text(group_id, "image_failed", &vars)
+                ));
+            }
         }
 
-        tokio::time::sleep(Duration::from_secs(10)).await;
-        delete(&remove_img_path).await;
+        // 观察模式只回复检测结果，不撤回
+        let is_delete =
+            !violations.is_empty() && !self.is_observing(group_id) && self.config.is_delete_message;
+        Some(Outcome {
+            detector: self,
+            reply: Some(msg),
+            ban: None,
+            is_delete,
+            is_repost: false,
+            redacted: Vec::new(),
+            tmp_files,
+        })
     }
 
-    pub(crate) async fn send_not_img(
+    /// 执法模式：记录次数并通知，返回回复、禁言与打码结果
+    ///
+    /// `is_repost` 为本群是否有检测器打码重发，此时即使本检测器只撤回也要提供打码结果。
+    async fn enforce(
         &self,
         e: &AllMsgEvent,
         bot: &RuntimeBot,
-        imgs_data: &Vec<(Vec<u8>, ImageFormat)>,
-    ) {
-        let mut msg = Message::from(&self.config.reply_msg);
-        let mut is_detected = false;
-
-        for (img_data, img_type) in imgs_data {
-            let prob = match img_type {
-                ImageFormat::Gif => {
-                    let img = extract_frame_from_gif_bytes(&img_data, 0).await.unwrap();
-                    match self.process_image(img) {
-                        Ok(v) => v,
-                        Err(err) => {
-                            error!("{}", err);
-                            return;
-                        }
-                    }
-                }
-                _ => {
-                    let original_img = image::load_from_memory(&img_data).unwrap();
-                    match self.process_image(original_img) {
-                        Ok(v) => v,
-                        Err(err) => {
-                            error!("{}", err);
-                            return;
-                        }
-                    }
-                }
-            };
-
-            info!("{} prob: {}", self.name, prob);
-
-            if prob >= self.config.trigger {
-                is_detected = true;
-                if self.config.is_reply_trigger {
-                    msg.push_text(format!("\n相似度：{:.2}", prob));
-                }
+        imgs_data: &[FetchedImage],
+        is_repost: bool,
+    ) -> Option<Outcome<'_>> {
+        let group_id = e.group_id.unwrap();
+        let results = self.detect_all(group_id, imgs_data).await;
+
+        let violations = violations(&results, self.config.trigger);
+        let is_detected = !violations.is_empty();
+
+        let total_images = results.len();
+        let violation_count = violations.len();
+        // 逐张说明，等次数更新后再拼上回复开头
+        let mut lines = String::new();
+        let mut scores = Vec::new();
+        // 发给管理员的标注图片
+        let mut annotated = Vec::new();
+        // 违规图片的打码结果，合并转发内的图片不打码
+        let mut redacted = Vec::new();
+
+        for (result, report, frames) in violations {
+            let img = result.img;
+            scores.push(report.score);
+            lines.push_str(&self.describe_image(group_id, result.index, img, report));
+            if self
+                .notifier
+                .is_enabled(group_id, self.is_observing(group_id))
+                && self.notifier.options().include_image
+            {
+                annotated.push(draw_report(report, frames, &self.config.render));
+            }
+            if is_repost {
+                let image = img
+                    .node
+                    .is_none()
+                    .then(|| RedactedImage::new(report, frames, self.config.repost_redaction));
+                redacted.push(Redacted {
+                    segment_index: img.segment_index,
+                    image,
+                });
             }
         }
 
         if !is_detected {
-            return;
+            return None;
+        }
+        self.record_today(group_id);
+
+        if self.is_observing(group_id) {
+            self.observe(e, bot, &scores, annotated).await;
+            return None;
         }
 
-        let group_id = e.group_id.unwrap();
         let user_id = e.user_id;
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        {
+        let is_raid = self.limiter.record_detection(&self.name, group_id, user_id);
+        let ban_duration = {
             let mut user_info_lock = self.user_info.lock().unwrap();
             let user_data = user_info_lock.entry(user_id).or_insert_with(|| UserInfo {
                 total_times: 0,
@@ -290,252 +707,369 @@ impl Detector {
             let last_timestamp = user_data.last_timestamp.get(&group_id).unwrap_or(&0);
             let time_diff = current_time - last_timestamp;
 
-            if time_diff < self.config.ban_cooldown {
-                bot.set_group_ban(group_id, user_id, self.config.ban_duration);
-                e.reply(self.config.ban_msg.deref());
-            }
+            // 刷屏时直接禁言，不受冷却时间限制
+            let ban_duration = if is_raid {
+                Some(self.limiter.options().raid_ban_duration)
+            } else if time_diff < self.config.ban_cooldown {
+                Some(self.config.ban_duration)
+            } else {
+                None
+            };
 
             user_data.update_time(group_id, current_time);
-        }
+            ban_duration
+        };
 
-        e.reply_and_quote(msg);
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        if self.config.is_delete_message {
-            bot.delete_msg(e.message_id);
+        let mut vars = self.reply_vars(e, &scores);
+        let mut ban = None;
+        if let Some(duration) = ban_duration {
+            vars = vars.ban(duration);
+            let ban_msg = if is_raid {
+                self.messages
+                    .configured(group_id, "raid_msg", &self.limiter.options().raid_msg)
+                    .message(&vars)
+            } else {
+                self.template_message(group_id, "ban_msg", &vars)
+            };
+            ban = Some((duration, ban_msg));
         }
+
+        self.notify(
+            e,
+            bot,
+            false,
+            &scores,
+            annotated,
+            &self.actions(group_id, ban_duration),
+        )
+        .await;
+
+        // 短时间内的再次违规合并到上一次回复中
+        let reply = self
+            .limiter
+            .should_reply(&self.name, group_id, user_id)
+            .then(|| {
+                let mut msg = self.reply_header(group_id, &vars, total_images, violation_count);
+                msg.push_text(&lines);
+                msg
+            });
+
+        Some(Outcome {
+            detector: self,
+            reply,
+            ban,
+            is_delete: self.config.is_delete_message,
+            is_repost: self.config.is_repost_redacted,
+            redacted,
+            tmp_files: Vec::new(),
+        })
     }
 
-    pub(crate) fn process_image_with_image(
+    /// 观察模式：只记录本会执行的处理，不回复、不撤回、不禁言
+    async fn observe(
         &self,
-        original_img: DynamicImage,
-    ) -> ort::Result<(image::ImageBuffer<image::Rgba<u8>, Vec<u8>>, f32)> {
-        let (img_width, img_height) = (original_img.width(), original_img.height());
-        let img = original_img.resize_exact(640, 640, FilterType::CatmullRom);
-
-        let mut input = Array::zeros((1, 3, 640, 640));
-        for pixel in img.pixels() {
-            let x = pixel.0 as _;
-            let y = pixel.1 as _;
-            let [r, g, b, _] = pixel.2 .0;
-            input[[0, 0, y, x]] = (r as f32) / 255.;
-            input[[0, 1, y, x]] = (g as f32) / 255.;
-            input[[0, 2, y, x]] = (b as f32) / 255.;
-        }
-
-        let outputs: SessionOutputs = self.model.run(inputs!["images" => input.view()]?)?;
-        let output = outputs["output0"]
-            .try_extract_tensor::<f32>()?
-            .t()
-            .into_owned();
-
-        let mut boxes = Vec::new();
-        let output = output.slice(s![.., .., 0]);
-        for row in output.axis_iter(Axis(0)) {
-            let row: Vec<_> = row.iter().copied().collect();
-            let (class_id, prob) = row
-                .iter()
-                .skip(4)
-                .enumerate()
-                .map(|(index, value)| (index, *value))
-                .reduce(|accum, row| if row.1 > accum.1 { row } else { accum })
-                .unwrap();
-
-            if prob < 0.3 {
-                continue;
-            }
+        e: &AllMsgEvent,
+        bot: &RuntimeBot,
+        scores: &[f32],
+        annotated: Vec<RgbaImage>,
+    ) {
+        let group_id = e.group_id.unwrap();
+        let user_id = e.user_id;
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
 
-            let label = self.labels[class_id];
-            let xc = row[0] / 640. * (img_width as f32);
-            let yc = row[1] / 640. * (img_height as f32);
-            let w = row[2] / 640. * (img_width as f32);
-            let h = row[3] / 640. * (img_height as f32);
-            boxes.push((
-                BoundingBox {
-                    x1: xc - w / 2.,
-                    y1: yc - h / 2.,
-                    x2: xc + w / 2.,
-                    y2: yc + h / 2.,
-                },
-                label,
-                prob,
-            ));
-        }
+        let would_ban = {
+            let mut user_info_lock = self.observe_user_info.lock().unwrap();
+            let user_data = user_info_lock.entry(user_id).or_insert_with(|| UserInfo {
+                total_times: 0,
+                group_total_times: HashMap::new(),
+                last_timestamp: HashMap::new(),
+            });
 
-        boxes.sort_by(|box1, box2| box2.2.total_cmp(&box1.2));
-        let mut result = Vec::new();
+            let last_timestamp = user_data.last_timestamp.get(&group_id).unwrap_or(&0);
+            let would_ban = current_time - last_timestamp < self.config.ban_cooldown;
+            user_data.update_time(group_id, current_time);
+            would_ban
+        };
 
-        while !boxes.is_empty() {
-            result.push(boxes[0]);
-            boxes = boxes
-                .iter()
-                .filter(|box1| {
-                    intersection(&boxes[0].0, &box1.0) / union(&boxes[0].0, &box1.0) < 0.7
-                })
-                .copied()
-                .collect();
-        }
+        let actions = self.actions(group_id, would_ban.then_some(self.config.ban_duration));
+        let report = self.describe_detection(e, scores, &actions);
+        info!("[观察] {}", report);
 
-        let mut max_prob = 0.0;
-        let mut dt = DrawTarget::new(img_width as _, img_height as _);
+        self.notify(e, bot, true, scores, annotated, &actions).await;
+    }
 
-        for (bbox, label, _confidence) in result {
-            if label == "xiong" {
-                continue;
-            }
+    /// 按配置本会执行的处理
+    fn actions(&self, group_id: i64, ban_duration: Option<usize>) -> Vec<String> {
+        let text = |key, vars: Vars| self.messages.text(group_id, key, &vars);
+        let mut actions = vec![text("action_reply", Vars::new())];
+        if self.config.is_repost_redacted {
+            actions.push(text("action_repost", Vars::new()));
+        } else if self.config.is_delete_message {
+            actions.push(text("action_delete", Vars::new()));
+        }
+        if let Some(duration) = ban_duration {
+            actions.push(text("action_ban", Vars::new().ban(duration)));
+        }
+        actions
+    }
 
-            if _confidence > max_prob {
-                max_prob = _confidence;
-            }
+    fn describe_detection(&self, e: &AllMsgEvent, scores: &[f32], actions: &[String]) -> String {
+        let group_id = e.group_id.unwrap_or_default();
+        let separator = self.messages.text(group_id, "separator", &Vars::new());
+        let scores: Vec<_> = scores.iter().map(|v| format!("{:.2}", v)).collect();
+        let vars = self
+            .vars(group_id)
+            .sender(e)
+            .set("group_id", group_id)
+            .set("scores", scores.join(&separator))
+            .set("actions", actions.join(&separator));
+        self.messages.text(group_id, "detection_report", &vars)
+    }
 
-            let mut pb = PathBuilder::new();
-            pb.rect(bbox.x1, bbox.y1, bbox.x2 - bbox.x1, bbox.y2 - bbox.y1);
-            let path = pb.finish();
+    /// 把检测记录与标注图片发到本群配置的通知位置
+    async fn notify(
+        &self,
+        e: &AllMsgEvent,
+        bot: &RuntimeBot,
+        is_observe: bool,
+        scores: &[f32],
+        annotated: Vec<RgbaImage>,
+        actions: &[String],
+    ) {
+        let group_id = e.group_id.unwrap();
+        if !self.notifier.is_enabled(group_id, is_observe) {
+            return;
+        }
 
-            let color = SolidSource {
-                r: 255,
-                g: 0,
-                b: 0,
-                a: 255,
+        let prefix = if is_observe {
+            self.messages.text(group_id, "observe_prefix", &Vars::new())
+        } else {
+            String::new()
+        };
+        let mut msg = Message::from(format!(
+            "{}{}",
+            prefix,
+            self.describe_detection(e, scores, actions)
+        ));
+        let mut tmp_files = Vec::new();
+        for (i, img) in annotated.iter().enumerate() {
+            let img_data = match encode_image(img, &self.config.render) {
+                Ok(v) => v,
+                Err(err) => {
+                    error!("{}", err);
+                    continue;
+                }
             };
-
-            dt.stroke(
-                &path,
-                &Source::Solid(color),
-                &StrokeStyle {
-                    join: LineJoin::Round,
-                    width: 4.,
-                    ..StrokeStyle::default()
-                },
-                &DrawOptions::new(),
-            );
-        }
-
-        let box_img: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> = image::RgbaImage::from_raw(
-            img_width,
-            img_height,
-            dt.get_data()
-                .iter()
-                .flat_map(|&p| {
-                    let a = (p >> 24) & 0xff;
-                    let r = (p >> 16) & 0xff;
-                    let g = (p >> 8) & 0xff;
-                    let b = p & 0xff;
-                    vec![r as u8, g as u8, b as u8, a as u8]
-                })
-                .collect(),
-        )
-        .unwrap();
-
-        let mut res_img = image::RgbaImage::new(img_width, img_height);
-        for (x, y, pixel) in res_img.enumerate_pixels_mut() {
-            let original_pixel = original_img.get_pixel(x, y);
-            let box_pixel = box_img.get_pixel(x, y);
-
-            if box_pixel.0[3] > 0 {
-                *pixel = image::Rgba([
-                    box_pixel.0[0],
-                    box_pixel.0[1],
-                    box_pixel.0[2],
-                    box_pixel.0[3],
-                ]);
-            } else {
-                *pixel = image::Rgba([
-                    original_pixel.0[0],
-                    original_pixel.0[1],
-                    original_pixel.0[2],
-                    255,
-                ]);
+            match self
+                .image_file(img_data, &format!("{}-notify", i), &mut tmp_files)
+                .await
+            {
+                Ok(file) => msg.push_image(&file),
+                Err(err) => error!("写入临时文件失败: {}", err),
             }
         }
 
-        Ok((res_img, max_prob))
+        self.notifier.send(bot, group_id, is_observe, msg);
+        TmpFile::release_later(tmp_files);
     }
 
-    pub(crate) fn process_image(&self, original_img: DynamicImage) -> ort::Result<f32> {
-        let img = original_img.resize_exact(640, 640, FilterType::CatmullRom);
+    /// 撤回原消息，保留文字与未违规的图片，违规图片替换为打码后的版本重发
+    ///
+    /// 含违规图片的合并转发无法重建，直接去掉；打码图片生成失败时只撤回不重发。
+    async fn repost_redacted(
+        &self,
+        e: &AllMsgEvent,
+        bot: &RuntimeBot,
+        msg: Option<Message>,
+        redacted: Vec<(usize, Option<RgbaImage>)>,
+    ) {
+        let group_id = e.group_id.unwrap();
+        let mut tmp_files = Vec::new();
+        let repost = match self.redacted_message(e, redacted, &mut tmp_files).await {
+            Ok(v) => Some(v),
+            Err(err) => {
+                error!("生成打码重发消息失败，只撤回: {}", err);
+                None
+            }
+        };
 
-        let mut input = Array::zeros((1, 3, 640, 640));
-        for pixel in img.pixels() {
-            let x = pixel.0 as _;
-            let y = pixel.1 as _;
-            let [r, g, b, _] = pixel.2 .0;
-            input[[0, 0, y, x]] = (r as f32) / 255.;
-            input[[0, 1, y, x]] = (g as f32) / 255.;
-            input[[0, 2, y, x]] = (b as f32) / 255.;
+        bot.delete_msg(e.message_id);
+        if let Some(msg) = msg {
+            e.reply(msg);
+        }
+        if let Some(repost) = repost {
+            bot.send_group_msg(group_id, repost);
         }
 
-        let outputs: SessionOutputs = self.model.run(inputs!["images" => input.view()]?)?;
-        let output = outputs["output0"]
-            .try_extract_tensor::<f32>()?
-            .t()
-            .into_owned();
+        TmpFile::release_later(tmp_files);
+    }
 
-        let mut max_prob = 0.0;
-        let output = output.slice(s![.., .., 0]);
-        for row in output.axis_iter(Axis(0)) {
-            let row: Vec<_> = row.iter().copied().collect();
-            let (class_id, prob) = row
-                .iter()
-                .skip(4)
-                .enumerate()
-                .map(|(index, value)| (index, *value))
-                .reduce(|accum, row| if row.1 > accum.1 { row } else { accum })
-                .unwrap();
+    async fn redacted_message(
+        &self,
+        e: &AllMsgEvent,
+        mut redacted: Vec<(usize, Option<RgbaImage>)>,
+        tmp_files: &mut Vec<TmpFile>,
+    ) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+        let group_id = e.group_id.unwrap();
+        let vars = self.vars(group_id).sender(e);
+        let mut repost = self.template_message(group_id, "repost_msg", &vars);
 
-            if self.labels[class_id] == self.labels[0] && prob > max_prob {
-                max_prob = prob;
-            }
+        for (i, segment) in e.message.iter().enumerate() {
+            let Some(pos) = redacted.iter().position(|(index, _)| *index == i) else {
+                repost.push(segment.clone());
+                continue;
+            };
+            let Some(img) = redacted.swap_remove(pos).1 else {
+                continue;
+            };
+            let img_data = encode_image(&img, &self.config.render)?;
+            let file = self
+                .image_file(img_data, &format!("{}-redacted", i), tmp_files)
+                .await?;
+            repost.push_image(&file);
         }
-
-        Ok(max_prob)
+        Ok(repost)
     }
-}
 
-pub(crate) fn intersection(box1: &BoundingBox, box2: &BoundingBox) -> f32 {
-    (box1.x2.min(box2.x2) - box1.x1.max(box2.x1)) * (box1.y2.min(box2.y2) - box1.y1.max(box2.y1))
-}
+    /// 返回可放入消息的图片：开启 `is_send_base64` 时直接内联，否则写入临时文件
+    async fn image_file(
+        &self,
+        img_data: Vec<u8>,
+        name: &str,
+        tmp_files: &mut Vec<TmpFile>,
+    ) -> std::io::Result<String> {
+        if self.config.is_send_base64 {
+            return Ok(format!("base64://{}", BASE64_STANDARD.encode(img_data)));
+        }
 
-pub(crate) fn union(box1: &BoundingBox, box2: &BoundingBox) -> f32 {
-    ((box1.x2 - box1.x1) * (box1.y2 - box1.y1)) + ((box2.x2 - box2.x1) * (box2.y2 - box2.y1))
-        - intersection(box1, box2)
+        let extension = self.config.render.output_format.extension();
+        let file = self.tmp.write(name, extension, &img_data).await?;
+        let path = file.path().to_str().unwrap().to_string();
+        tmp_files.push(file);
+        Ok(path)
+    }
 }
 
-pub(crate) async fn download_img(
-    url: &str,
-) -> Result<(Vec<u8>, ImageFormat), Box<dyn std::error::Error>> {
-    let response = reqwest::get(url).await?;
-    if response.status().is_success() {
-        let content = response.bytes().await?;
-        let img_type = image::guess_format(&content)?;
-        Ok((content.to_vec(), img_type))
-    } else {
-        Err("请求失败".into())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{build_report, EngineOptions};
+    use image::Rgba;
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8 * 4, y as u8 * 4, 0, 255]))
     }
-}
 
-pub(crate) async fn extract_frame_from_gif_bytes(
-    data: &[u8],
-    frame_index: usize,
-) -> Result<DynamicImage, Box<dyn std::error::Error>> {
-    let cursor = Cursor::new(data);
-    let decoder = GifDecoder::new(cursor)?;
-    let frames = decoder.into_frames().collect_frames()?;
+    fn bbox(x1: f32, y1: f32) -> BoundingBox {
+        BoundingBox {
+            x1,
+            y1,
+            x2: x1 + 20.,
+            y2: y1 + 20.,
+        }
+    }
 
-    if frame_index >= frames.len() {
-        return Err("Frame index out of bounds".into());
+    fn redacted(segment_index: usize, score: f32, boxes: Vec<BoundingBox>) -> Redacted {
+        let mut img = gradient();
+        for bbox in &boxes {
+            redact(&mut img, bbox, Redaction::Mosaic);
+        }
+        Redacted {
+            segment_index,
+            image: Some(RedactedImage {
+                score,
+                img,
+                boxes,
+                redaction: Redaction::Mosaic,
+            }),
+        }
     }
 
-    let frame = frames[frame_index].clone();
-    let dynamic_image = DynamicImage::ImageRgba8(frame.into_buffer());
+    #[test]
+    fn merge_redacted_combines_detectors() {
+        let merged = merge_redacted(vec![
+            redacted(3, 0.8, vec![bbox(40., 40.)]),
+            redacted(1, 0.8, vec![bbox(8., 8.)]),
+            redacted(1, 0.9, vec![bbox(40., 40.)]),
+            Redacted {
+                segment_index: 3,
+                image: None,
+            },
+            redacted(5, 0.9, vec![bbox(8., 8.)]),
+        ]);
+
+        let indexes: Vec<_> = merged.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indexes, [1, 3, 5]);
+
+        // 两个检测器的打码区域都生效
+        let original = gradient();
+        let img = merged[0].1.as_ref().unwrap();
+        assert_ne!(img.get_pixel(8, 8), original.get_pixel(8, 8));
+        assert_ne!(img.get_pixel(40, 40), original.get_pixel(40, 40));
+        assert_eq!(img.get_pixel(0, 63), original.get_pixel(0, 63));
+
+        // 合并转发内的图片无法重建
+        assert!(merged[1].1.is_none());
+
+        let img = merged[2].1.as_ref().unwrap();
+        assert_ne!(img.get_pixel(8, 8), original.get_pixel(8, 8));
+        assert_eq!(img.get_pixel(40, 40), original.get_pixel(40, 40));
+    }
 
-    Ok(dynamic_image)
-}
+    #[test]
+    fn check_and_enforcement_agree() {
+        let labels = ["loong", "xiong"].map(String::from).to_vec();
+        let frames = vec![DynamicImage::new_rgba8(640, 640)];
+        // 每行为 `[xc, yc, w, h, loong, xiong]`
+        let output = ndarray::arr2(&[
+            [100., 100., 100., 100., 0.9, 0.],
+            [105., 100., 100., 100., 0.8, 0.],
+            [400., 400., 100., 100., 0.95, 0.],
+            [402., 400., 100., 100., 0., 0.99],
+            [300., 100., 50., 50., 0.2, 0.],
+        ]);
+        let report = build_report(
+            "long",
+            &EngineOptions::long(),
+            &labels,
+            &frames,
+            std::iter::once(Ok(output)),
+        )
+        .unwrap();
 
-pub(crate) async fn delete(remove_img_path: &Vec<PathBuf>) {
-    for path in remove_img_path {
-        if let Err(err) = tokio::fs::remove_file(&path).await {
-            error!("{}", err);
-            error!("path {}", path.to_str().unwrap());
+        let img = FetchedImage {
+            data: Vec::new(),
+            segment_index: 0,
+            node: None,
         };
+        let results = vec![ImageResult {
+            index: 1,
+            img: &img,
+            result: Ok((report, frames)),
+        }];
+        let violations = violations(&results, 0.78);
+        assert_eq!(violations.len(), 1);
+        let (_, report, frames) = violations[0];
+
+        // 检测模式回复的分数与标注框
+        let checked: Vec<_> = best_frame(report).1.iter().map(|d| d.bbox).collect();
+        // 执法模式转发的打码图片
+        let enforced = RedactedImage::new(report, frames, Redaction::Mosaic);
+
+        assert_eq!(enforced.score, report.score);
+        assert_eq!(enforced.boxes, checked);
+        assert_eq!(report.score, 0.9);
+        assert_eq!(
+            checked,
+            [BoundingBox {
+                x1: 50.,
+                y1: 50.,
+                x2: 150.,
+                y2: 150.,
+            }]
+        );
     }
 }
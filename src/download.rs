@@ -0,0 +1,312 @@
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use image::{ImageFormat, ImageReader};
+use kovi::log::info;
+use kovi::serde_json::{json, Value};
+use kovi::{tokio, RuntimeBot};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+
+/// 图片下载配置，保存在 `download_config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct DownloadOptions {
+    /// 建立连接的超时时间（秒）
+    pub(crate) connect_timeout_secs: u64,
+    /// 两次读取之间的超时时间（秒）
+    pub(crate) read_timeout_secs: u64,
+    /// 单次请求的总超时时间（秒）
+    pub(crate) timeout_secs: u64,
+    /// 图片最大字节数
+    pub(crate) max_body_bytes: u64,
+    /// 图片最大像素数（宽 × 高），在完整解码前检查
+    pub(crate) max_pixels: u64,
+    /// 失败后的重试次数
+    pub(crate) retries: u32,
+    /// 首次重试前的等待时间（毫秒），之后每次翻倍
+    pub(crate) retry_backoff_ms: u64,
+    /// 合并转发最多展开的层数
+    pub(crate) forward_max_depth: usize,
+    /// 单条消息最多检测的图片数，包括合并转发内的图片
+    pub(crate) max_images: usize,
+    /// 是否检测视频，未启用 `video` 功能时只检测封面
+    pub(crate) is_scan_video: bool,
+    /// 视频最大字节数
+    pub(crate) video_max_bytes: u64,
+    /// 每个视频最多检测的关键帧数，均匀抽取
+    pub(crate) video_frames: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 5,
+            read_timeout_secs: 10,
+            timeout_secs: 30,
+            max_body_bytes: 20 * 1024 * 1024,
+            max_pixels: 40_000_000,
+            retries: 2,
+            retry_backoff_ms: 500,
+            forward_max_depth: 3,
+            max_images: 20,
+            is_scan_video: false,
+            video_max_bytes: 50 * 1024 * 1024,
+            video_frames: 3,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum DownloadError {
+    /// 连接、超时等网络错误
+    Request(reqwest::Error),
+    /// 服务器返回了非成功状态码
+    Status(StatusCode),
+    /// 图片超过大小上限
+    TooLarge(u64),
+    /// 无法识别图片格式或读取尺寸
+    Image(image::ImageError),
+    /// 图片像素数超过上限
+    TooManyPixels(u32, u32),
+    /// `get_image` 接口调用失败或返回内容无法使用
+    Api(String),
+    /// 读取本地文件失败
+    Io(std::io::Error),
+    /// 消息段中没有可用的图片来源
+    NoSource,
+}
+
+impl DownloadError {
+    /// 网络错误与服务器错误可以重试，其余错误重试也不会成功
+    fn is_retryable(&self) -> bool {
+        match self {
+            DownloadError::Request(_) => true,
+            DownloadError::Status(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Request(err) => write!(f, "请求失败: {}", err),
+            DownloadError::Status(status) => write!(f, "请求失败，状态码: {}", status),
+            DownloadError::TooLarge(limit) => write!(f, "图片超过 {} 字节", limit),
+            DownloadError::Image(err) => write!(f, "无法识别图片: {}", err),
+            DownloadError::TooManyPixels(w, h) => write!(f, "图片尺寸过大: {}x{}", w, h),
+            DownloadError::Api(msg) => write!(f, "get_image 失败: {}", msg),
+            DownloadError::Io(err) => write!(f, "读取本地图片失败: {}", err),
+            DownloadError::NoSource => write!(f, "消息段中没有 url 或 file"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DownloadError::Request(err) => Some(err),
+            DownloadError::Image(err) => Some(err),
+            DownloadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(err: reqwest::Error) -> Self {
+        DownloadError::Request(err)
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(err: std::io::Error) -> Self {
+        DownloadError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for DownloadError {
+    fn from(err: image::ImageError) -> Self {
+        DownloadError::Image(err)
+    }
+}
+
+/// 共用连接池的图片下载器
+#[derive(Clone)]
+pub(crate) struct Downloader {
+    client: reqwest::Client,
+    options: DownloadOptions,
+}
+
+impl Downloader {
+    pub(crate) fn new(options: DownloadOptions) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(options.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(options.read_timeout_secs))
+            .timeout(Duration::from_secs(options.timeout_secs))
+            .build()
+            .unwrap();
+
+        Self { client, options }
+    }
+
+    pub(crate) fn options(&self) -> &DownloadOptions {
+        &self.options
+    }
+
+    /// 按 `url`、`get_image` 接口、本地路径的顺序获取图片消息段的内容
+    pub(crate) async fn fetch(
+        &self,
+        bot: &RuntimeBot,
+        data: &Value,
+    ) -> Result<(Vec<u8>, ImageFormat), DownloadError> {
+        let url = data
+            .get("url")
+            .and_then(Value::as_str)
+            .filter(|v| !v.is_empty());
+        let file = data
+            .get("file")
+            .and_then(Value::as_str)
+            .filter(|v| !v.is_empty());
+        let mut last_err = DownloadError::NoSource;
+
+        if let Some(url) = url {
+            match self.download(url).await {
+                Ok(v) => return Ok(v),
+                // 图片本身不合格时换来源也没有意义
+                Err(err @ (DownloadError::TooLarge(_) | DownloadError::TooManyPixels(..))) => {
+                    return Err(err)
+                }
+                Err(err) => {
+                    info!("通过 url 获取图片失败，尝试 get_image: {}", err);
+                    last_err = err;
+                }
+            }
+        }
+
+        let Some(file) = file else {
+            return Err(last_err);
+        };
+
+        match self.get_image(bot, file).await {
+            Ok(v) => return Ok(v),
+            Err(err) => {
+                info!("通过 get_image 获取图片失败，尝试本地路径: {}", err);
+                last_err = err;
+            }
+        }
+
+        let path = file.strip_prefix("file://").unwrap_or(file);
+        if Path::new(path).is_absolute() {
+            return self.read_local(path).await;
+        }
+        Err(last_err)
+    }
+
+    /// 调用 OneBot 的 `get_image`，返回内容可能是 base64 或实现所在机器上的路径
+    async fn get_image(
+        &self,
+        bot: &RuntimeBot,
+        file: &str,
+    ) -> Result<(Vec<u8>, ImageFormat), DownloadError> {
+        let ret = bot
+            .send_api_return("get_image", json!({ "file": file }))
+            .await
+            .map_err(|ret| DownloadError::Api(ret.to_string()))?;
+
+        if let Some(base64) = ret.data.get("base64").and_then(Value::as_str) {
+            let content = BASE64_STANDARD
+                .decode(base64)
+                .map_err(|err| DownloadError::Api(err.to_string()))?;
+            return self.check(content);
+        }
+
+        match ret.data.get("file").and_then(Value::as_str) {
+            Some(path) => self.read_local(path).await,
+            None => Err(DownloadError::Api("返回内容中没有 file".to_string())),
+        }
+    }
+
+    async fn read_local(&self, path: &str) -> Result<(Vec<u8>, ImageFormat), DownloadError> {
+        let limit = self.options.max_body_bytes;
+        if tokio::fs::metadata(path).await?.len() > limit {
+            return Err(DownloadError::TooLarge(limit));
+        }
+        self.check(tokio::fs::read(path).await?)
+    }
+
+    /// 下载图片，失败时按指数退避重试
+    pub(crate) async fn download(
+        &self,
+        url: &str,
+    ) -> Result<(Vec<u8>, ImageFormat), DownloadError> {
+        let content = self
+            .download_bytes(url, self.options.max_body_bytes)
+            .await?;
+        self.check(content)
+    }
+
+    /// 下载视频，大小上限为 `video_max_bytes`
+    pub(crate) async fn download_video(&self, url: &str) -> Result<Vec<u8>, DownloadError> {
+        self.download_bytes(url, self.options.video_max_bytes).await
+    }
+
+    async fn download_bytes(&self, url: &str, limit: u64) -> Result<Vec<u8>, DownloadError> {
+        let mut backoff = Duration::from_millis(self.options.retry_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            match self.try_download(url, limit).await {
+                Err(err) if err.is_retryable() && attempt < self.options.retries => {
+                    attempt += 1;
+                    info!("下载失败，{:?} 后第 {} 次重试: {}", backoff, attempt, err);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn try_download(&self, url: &str, limit: u64) -> Result<Vec<u8>, DownloadError> {
+        let mut response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(DownloadError::Status(response.status()));
+        }
+
+        if response.content_length().is_some_and(|len| len > limit) {
+            return Err(DownloadError::TooLarge(limit));
+        }
+
+        // 分块读取，不信任 Content-Length
+        let mut content = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (content.len() + chunk.len()) as u64 > limit {
+                return Err(DownloadError::TooLarge(limit));
+            }
+            content.extend_from_slice(&chunk);
+        }
+
+        Ok(content)
+    }
+
+    /// 识别格式并在解码前检查尺寸，防止解压炸弹
+    fn check(&self, content: Vec<u8>) -> Result<(Vec<u8>, ImageFormat), DownloadError> {
+        if content.len() as u64 > self.options.max_body_bytes {
+            return Err(DownloadError::TooLarge(self.options.max_body_bytes));
+        }
+
+        let img_type = image::guess_format(&content)?;
+        let (width, height) =
+            ImageReader::with_format(Cursor::new(&content), img_type).into_dimensions()?;
+        if width as u64 * height as u64 > self.options.max_pixels {
+            return Err(DownloadError::TooManyPixels(width, height));
+        }
+
+        Ok((content, img_type))
+    }
+}
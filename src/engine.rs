@@ -0,0 +1,497 @@
+use image::codecs::gif::GifDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, GenericImageView, ImageFormat};
+use kovi::log::info;
+use kovi::tokio;
+use ndarray::{s, Array, Array2, Ix2};
+use ort::inputs;
+use ort::session::builder::GraphOptimizationLevel;
+use ort::session::{Session, SessionOutputs};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use crate::pool::SessionPool;
+use crate::postprocess::{
+    postprocess, score, DetectionReport, LabelKind, PostprocessOptions, INPUT_SIZE,
+};
+use crate::{LONG_MODEL, NAILONG_MODEL};
+
+/// 推理引擎配置，平铺在检测器配置中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineOptions {
+    /// 推理会话池大小
+    pub session_pool_size: usize,
+    /// 每个推理会话使用的线程数
+    pub session_intra_threads: usize,
+    /// 违规类别，为空时以模型的第一个类别为违规类别
+    pub violation_labels: Vec<String>,
+    /// 负类别
+    pub negative_labels: Vec<String>,
+    /// 忽略类别
+    pub ignored_labels: Vec<String>,
+    /// GIF 最多检测的帧数，均匀抽取
+    pub gif_frames: usize,
+    #[serde(flatten)]
+    pub postprocess: PostprocessOptions,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            session_pool_size: 2,
+            session_intra_threads: 4,
+            violation_labels: Vec::new(),
+            negative_labels: Vec::new(),
+            ignored_labels: Vec::new(),
+            gif_frames: 1,
+            postprocess: PostprocessOptions::default(),
+        }
+    }
+}
+
+impl EngineOptions {
+    /// 龙图模型的默认配置，`xiong` 作为负类别
+    pub fn long() -> Self {
+        Self {
+            violation_labels: vec!["loong".to_string()],
+            negative_labels: vec!["xiong".to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// 奶龙模型的默认配置
+    pub fn nailong() -> Self {
+        Self {
+            violation_labels: vec!["nailong".to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// 类别在检测器中的用途，`labels` 为模型的类别名
+    pub(crate) fn label_kind(&self, labels: &[String], class_id: usize) -> LabelKind {
+        let label = labels.get(class_id).map(String::as_str).unwrap_or("");
+        let contains = |labels: &Vec<String>| labels.iter().any(|v| v == label);
+
+        if contains(&self.ignored_labels) {
+            LabelKind::Ignored
+        } else if contains(&self.negative_labels) {
+            LabelKind::Negative
+        } else if contains(&self.violation_labels)
+            || (self.violation_labels.is_empty() && class_id == 0)
+        {
+            LabelKind::Violation
+        } else {
+            LabelKind::Ignored
+        }
+    }
+
+    /// 升级前的配置文件没有类别配置，改用 `defaults` 中的类别，否则 `xiong` 会被当作忽略类别
+    pub fn migrate_labels(&mut self, defaults: &EngineOptions) {
+        if self.violation_labels.is_empty()
+            && self.negative_labels.is_empty()
+            && self.ignored_labels.is_empty()
+        {
+            self.violation_labels = defaults.violation_labels.clone();
+            self.negative_labels = defaults.negative_labels.clone();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DetectError {
+    /// 模型加载或推理失败
+    Ort(ort::Error),
+    /// 图片解码失败
+    Image(image::ImageError),
+    /// 图片中没有可检测的帧
+    NoFrames,
+    /// 推理线程异常退出，附带原因
+    Join(String),
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectError::Ort(err) => write!(f, "推理失败: {}", err),
+            DetectError::Image(err) => write!(f, "图片解码失败: {}", err),
+            DetectError::NoFrames => write!(f, "图片中没有可检测的帧"),
+            DetectError::Join(err) => write!(f, "推理任务异常退出: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DetectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DetectError::Ort(err) => Some(err),
+            DetectError::Image(err) => Some(err),
+            DetectError::NoFrames | DetectError::Join(_) => None,
+        }
+    }
+}
+
+impl From<ort::Error> for DetectError {
+    fn from(err: ort::Error) -> Self {
+        DetectError::Ort(err)
+    }
+}
+
+impl From<image::ImageError> for DetectError {
+    fn from(err: image::ImageError) -> Self {
+        DetectError::Image(err)
+    }
+}
+
+/// 不依赖 kovi 的推理引擎，可在其他插件或命令行中直接使用
+#[derive(Clone)]
+pub struct Engine {
+    pool: Arc<SessionPool>,
+    labels: Arc<Vec<String>>,
+    options: Arc<EngineOptions>,
+    name: Arc<String>,
+    version: Arc<String>,
+}
+
+impl Engine {
+    /// 从模型字节创建引擎，模型缺少类别元数据时使用 `fallback_labels`
+    pub fn new(
+        name: &str,
+        model_bytes: &[u8],
+        fallback_labels: &[&str],
+        options: EngineOptions,
+    ) -> Result<Self, DetectError> {
+        let sessions = (0..options.session_pool_size.max(1))
+            .map(|_| {
+                Session::builder()?
+                    .with_optimization_level(GraphOptimizationLevel::Level3)?
+                    .with_intra_threads(options.session_intra_threads.max(1))?
+                    .commit_from_memory(model_bytes)
+            })
+            .collect::<ort::Result<Vec<_>>>()?;
+
+        // 优先使用模型元数据中的类别名（Ultralytics 导出的 names）
+        let labels = match read_model_labels(&sessions[0]) {
+            Some(labels) => labels,
+            None => {
+                info!("{} 模型缺少类别元数据，使用内置类别", name);
+                fallback_labels.iter().map(|v| v.to_string()).collect()
+            }
+        };
+        info!("{} 模型类别: {:?}", name, labels);
+        let version = read_model_version(&sessions[0], model_bytes);
+        info!("{} 模型版本: {}", name, version);
+
+        Ok(Self {
+            pool: Arc::new(SessionPool::new(sessions)),
+            labels: Arc::new(labels),
+            options: Arc::new(options),
+            name: Arc::new(name.to_string()),
+            version: Arc::new(version),
+        })
+    }
+
+    /// 内置龙图模型
+    pub fn long(options: EngineOptions) -> Result<Self, DetectError> {
+        Self::new("龙图", LONG_MODEL, &["loong", "xiong"], options)
+    }
+
+    /// 内置奶龙模型
+    pub fn nailong(options: EngineOptions) -> Result<Self, DetectError> {
+        Self::new("奶龙", NAILONG_MODEL, &["nailong"], options)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 模型版本：导出时的元数据与模型内容的校验值
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn options(&self) -> &EngineOptions {
+        &self.options
+    }
+
+    /// 检测一张图片（支持 GIF），返回检测报告
+    pub async fn detect(&self, img_data: &[u8]) -> Result<DetectionReport, DetectError> {
+        self.detect_in(0, img_data).await
+    }
+
+    /// [`Engine::detect`] 的阻塞版本，不能在异步运行时中调用
+    pub fn detect_blocking(&self, img_data: &[u8]) -> Result<DetectionReport, DetectError> {
+        let frames = decode_frames(img_data, self.options.gif_frames)?;
+        let session = self.pool.acquire_blocking(0);
+        self.detect_frames_with(&session, &frames)
+    }
+
+    /// 以指定群的身份排队推理
+    pub(crate) async fn detect_in(
+        &self,
+        group_id: i64,
+        img_data: &[u8],
+    ) -> Result<DetectionReport, DetectError> {
+        let frames = decode_frames_async(img_data, self.options.gif_frames).await?;
+        let (report, _) = self.detect_frames(group_id, frames).await?;
+        Ok(report)
+    }
+
+    /// 检测已解码的帧，并把帧交还给调用方用于绘制
+    pub(crate) async fn detect_frames(
+        &self,
+        group_id: i64,
+        frames: Vec<DynamicImage>,
+    ) -> Result<(DetectionReport, Vec<DynamicImage>), DetectError> {
+        let session = self.pool.acquire(group_id).await;
+        let engine = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let report = engine.detect_frames_with(&session, &frames)?;
+            Ok((report, frames))
+        })
+        .await
+        .map_err(|err| DetectError::Join(err.to_string()))?
+    }
+
+    fn detect_frames_with(
+        &self,
+        session: &Session,
+        frames: &[DynamicImage],
+    ) -> Result<DetectionReport, DetectError> {
+        // 逐帧推理，不保留所有帧的原始输出
+        let outputs = frames.iter().map(|frame| run_model(session, frame));
+        build_report(&self.name, &self.options, &self.labels, frames, outputs)
+    }
+
+    /// 该类别是否按配置计为违规
+    pub fn is_violation_class(&self, class_id: usize) -> bool {
+        self.label_kind(class_id) == LabelKind::Violation
+    }
+
+    pub(crate) fn label_kind(&self, class_id: usize) -> LabelKind {
+        self.options.label_kind(&self.labels, class_id)
+    }
+}
+
+/// 由各帧的模型输出生成检测报告，检测模式与执法模式都使用这份报告
+pub(crate) fn build_report(
+    detector: &str,
+    options: &EngineOptions,
+    labels: &[String],
+    frames: &[DynamicImage],
+    outputs: impl Iterator<Item = ort::Result<Array2<f32>>>,
+) -> Result<DetectionReport, DetectError> {
+    let first = frames.first().ok_or(DetectError::NoFrames)?;
+
+    let mut detections = Vec::new();
+    for (frame_index, (frame, output)) in frames.iter().zip(outputs).enumerate() {
+        detections.extend(postprocess(
+            output?.view(),
+            frame.width(),
+            frame.height(),
+            frame_index,
+            &options.postprocess,
+            labels,
+            |class_id| options.label_kind(labels, class_id),
+        ));
+    }
+
+    Ok(DetectionReport {
+        detector: detector.to_string(),
+        score: score(&detections),
+        detections,
+        frames: frames.len(),
+        width: first.width(),
+        height: first.height(),
+    })
+}
+
+/// 运行模型，返回 `[候选框, 4 + 类别数]` 形状的原始输出
+fn run_model(session: &Session, original_img: &DynamicImage) -> ort::Result<Array2<f32>> {
+    let img = original_img.resize_exact(INPUT_SIZE, INPUT_SIZE, FilterType::CatmullRom);
+
+    let mut input = Array::zeros((1, 3, INPUT_SIZE as usize, INPUT_SIZE as usize));
+    for pixel in img.pixels() {
+        let x = pixel.0 as _;
+        let y = pixel.1 as _;
+        let [r, g, b, _] = pixel.2 .0;
+        input[[0, 0, y, x]] = (r as f32) / 255.;
+        input[[0, 1, y, x]] = (g as f32) / 255.;
+        input[[0, 2, y, x]] = (b as f32) / 255.;
+    }
+
+    let outputs: SessionOutputs = session.run(inputs!["images" => input.view()]?)?;
+    let output = outputs["output0"]
+        .try_extract_tensor::<f32>()?
+        .t()
+        .into_owned();
+
+    output
+        .slice(s![.., .., 0])
+        .into_owned()
+        .into_dimensionality::<Ix2>()
+        .map_err(ort::Error::wrap)
+}
+
+/// GIF 最多解码的帧数，超出的帧不再检测
+const MAX_GIF_FRAMES: usize = 500;
+
+/// 解码图片，GIF 均匀抽取至多 `max_frames` 帧
+///
+/// GIF 逐帧解码，只保留抽中的帧，避免大量大尺寸帧同时占用内存。
+pub(crate) fn decode_frames(
+    img_data: &[u8],
+    max_frames: usize,
+) -> Result<Vec<DynamicImage>, DetectError> {
+    if image::guess_format(img_data)? != ImageFormat::Gif {
+        return Ok(vec![image::load_from_memory(img_data)?]);
+    }
+
+    let wanted = max_frames.max(1);
+    let decoder = GifDecoder::new(Cursor::new(img_data))?;
+    // 帧数未知，按间隔保留，保留过多时间隔翻倍，保留的帧始终均匀分布
+    let mut kept = Vec::new();
+    let mut stride = 1;
+    for (i, frame) in decoder.into_frames().take(MAX_GIF_FRAMES).enumerate() {
+        let frame = frame?;
+        if i % stride != 0 {
+            continue;
+        }
+        kept.push(frame);
+        if wanted == 1 {
+            break;
+        }
+        if kept.len() >= wanted * 2 {
+            kept = kept.into_iter().step_by(2).collect();
+            stride *= 2;
+        }
+    }
+    if kept.is_empty() {
+        return Err(DetectError::NoFrames);
+    }
+
+    let wanted = wanted.min(kept.len());
+    let step = kept.len() / wanted;
+    Ok(kept
+        .into_iter()
+        .step_by(step)
+        .take(wanted)
+        .map(|frame| DynamicImage::ImageRgba8(frame.into_buffer()))
+        .collect())
+}
+
+/// 在阻塞线程中解码，避免占用异步运行时
+pub(crate) async fn decode_frames_async(
+    img_data: &[u8],
+    max_frames: usize,
+) -> Result<Vec<DynamicImage>, DetectError> {
+    let img_data = img_data.to_vec();
+    tokio::task::spawn_blocking(move || decode_frames(&img_data, max_frames))
+        .await
+        .map_err(|err| DetectError::Join(err.to_string()))?
+}
+
+/// 读取 Ultralytics 导出时写入的 `names` 元数据，形如 `{0: 'loong', 1: 'xiong'}`
+fn read_model_labels(session: &Session) -> Option<Vec<String>> {
+    let names = session.metadata().ok()?.custom("names").ok()??;
+    parse_names(&names)
+}
+
+/// Ultralytics 导出时写入 `version` 与 `date`，其他模型使用 ONNX 的模型版本号
+fn read_model_version(session: &Session, model_bytes: &[u8]) -> String {
+    // FNV-1a，与编译器版本无关，便于比对不同部署的模型
+    let hash = model_bytes.iter().fold(0xcbf29ce484222325u64, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    let checksum = format!("{:016x}", hash);
+
+    let Ok(metadata) = session.metadata() else {
+        return checksum;
+    };
+    let custom = |key| metadata.custom(key).ok().flatten();
+    let exported = match (custom("version"), custom("date")) {
+        (Some(version), Some(date)) => format!("{} {}", version, date),
+        (Some(v), None) | (None, Some(v)) => v,
+        (None, None) => match metadata.version() {
+            Ok(version) => format!("v{}", version),
+            Err(_) => return checksum,
+        },
+    };
+    format!("{} ({})", exported, checksum)
+}
+
+fn parse_names(names: &str) -> Option<Vec<String>> {
+    let body = names.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut labels = Vec::new();
+    let mut rest = body;
+
+    loop {
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        let (index, tail) = rest.split_once(':')?;
+        let index: usize = index.trim().parse().ok()?;
+
+        let tail = tail.trim_start();
+        let quote = tail.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+        let tail = &tail[1..];
+        let end = tail.find(quote)?;
+
+        if index != labels.len() {
+            return None;
+        }
+        labels.push(tail[..end].to_string());
+        rest = &tail[end + 1..];
+    }
+
+    if labels.is_empty() {
+        None
+    } else {
+        Some(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_names_in_order() {
+        assert_eq!(
+            parse_names("{0: 'long', 1: \"not_long\"}"),
+            Some(vec!["long".to_string(), "not_long".to_string()])
+        );
+    }
+
+    #[test]
+    fn parse_names_rejects_invalid() {
+        assert_eq!(parse_names("{1: 'long'}"), None);
+        assert_eq!(parse_names("{0: long}"), None);
+        assert_eq!(parse_names("{}"), None);
+        assert_eq!(parse_names("long"), None);
+    }
+
+    #[test]
+    fn migrate_labels_only_when_unset() {
+        let mut legacy = EngineOptions::default();
+        legacy.migrate_labels(&EngineOptions::long());
+        assert_eq!(legacy.violation_labels, ["loong"]);
+        assert_eq!(legacy.negative_labels, ["xiong"]);
+
+        let mut configured = EngineOptions {
+            ignored_labels: vec!["xiong".to_string()],
+            ..EngineOptions::default()
+        };
+        configured.migrate_labels(&EngineOptions::long());
+        assert!(configured.violation_labels.is_empty());
+        assert!(configured.negative_labels.is_empty());
+    }
+}
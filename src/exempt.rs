@@ -0,0 +1,179 @@
+use kovi::serde_json::Value;
+use kovi::{AllMsgEvent, RuntimeBot};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::locale::Messages;
+use crate::permission::Permissions;
+use crate::template::Vars;
+
+/// 一个群的豁免名单，豁免的成员不会被计数、禁言或撤回
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Exemption {
+    pub(crate) users: Vec<i64>,
+    /// 群身份：`owner`、`admin`
+    pub(crate) roles: Vec<String>,
+}
+
+impl Default for Exemption {
+    fn default() -> Self {
+        // bot 无法禁言群主和管理员，默认豁免
+        Self {
+            users: Vec::new(),
+            roles: vec!["owner".to_string(), "admin".to_string()],
+        }
+    }
+}
+
+/// 各群的豁免名单，保存在 `exemptions.json`
+#[derive(Clone)]
+pub(crate) struct Exemptions {
+    pub(crate) groups: Arc<RwLock<HashMap<i64, Exemption>>>,
+}
+
+impl Exemptions {
+    pub(crate) fn new(groups: HashMap<i64, Exemption>) -> Self {
+        Self {
+            groups: Arc::new(RwLock::new(groups)),
+        }
+    }
+
+    pub(crate) fn is_exempt(&self, e: &AllMsgEvent) -> bool {
+        let Some(group_id) = e.group_id else {
+            return false;
+        };
+        let groups = self.groups.read().unwrap();
+        let default = Exemption::default();
+        let exemption = groups.get(&group_id).unwrap_or(&default);
+
+        exemption.users.contains(&e.user_id)
+            || e.sender
+                .role
+                .as_ref()
+                .is_some_and(|role| exemption.roles.contains(role))
+    }
+
+    /// 处理豁免命令：`<命令> @成员`、`<命令> QQ号`、`<命令> admin`
+    pub(crate) async fn handle_command(
+        &self,
+        e: &AllMsgEvent,
+        bot: &RuntimeBot,
+        permissions: &Permissions,
+    ) {
+        let Some(group_id) = e.group_id else {
+            return;
+        };
+        let Some(text) = e.borrow_text() else {
+            return;
+        };
+        let mut args = text.split_whitespace();
+        let Some(cmd) = args.next() else {
+            return;
+        };
+
+        let options = permissions.options();
+        let is_add = cmd == options.exempt_cmd;
+        let is_remove = cmd == options.unexempt_cmd;
+        let is_list = cmd == options.exempt_list_cmd;
+        if !is_add && !is_remove && !is_list {
+            return;
+        }
+
+        if !permissions.check(bot, e).await {
+            return;
+        }
+
+        let messages = permissions.messages();
+        if is_list {
+            e.reply(self.describe(group_id, messages));
+            return;
+        }
+
+        // @ 的成员与文本中的 QQ 号都视为用户，其余视为群身份
+        let mut users: Vec<i64> = e
+            .message
+            .get("at")
+            .iter()
+            .filter_map(|at| at.data.get("qq").and_then(Value::as_str)?.parse().ok())
+            .collect();
+        let mut roles = Vec::new();
+        for arg in args {
+            match arg.parse::<i64>() {
+                Ok(user_id) => users.push(user_id),
+                Err(_) => match parse_role(arg) {
+                    Some(role) => roles.push(role.to_string()),
+                    None => {
+                        let vars = Vars::new().set("arg", arg);
+                        e.reply(messages.text(group_id, "exempt_bad_arg", &vars));
+                        return;
+                    }
+                },
+            }
+        }
+
+        if users.is_empty() && roles.is_empty() {
+            let vars = Vars::new().set("cmd", cmd);
+            e.reply(messages.text(group_id, "exempt_usage", &vars));
+            return;
+        }
+
+        {
+            let mut groups = self.groups.write().unwrap();
+            let exemption = groups.entry(group_id).or_default();
+            if is_add {
+                for user_id in users {
+                    if !exemption.users.contains(&user_id) {
+                        exemption.users.push(user_id);
+                    }
+                }
+                for role in roles {
+                    if !exemption.roles.contains(&role) {
+                        exemption.roles.push(role);
+                    }
+                }
+            } else {
+                exemption.users.retain(|v| !users.contains(v));
+                exemption.roles.retain(|v| !roles.contains(v));
+            }
+        }
+
+        e.reply(self.describe(group_id, messages));
+    }
+
+    fn describe(&self, group_id: i64, messages: &Messages) -> String {
+        let groups = self.groups.read().unwrap();
+        let default = Exemption::default();
+        let exemption = groups.get(&group_id).unwrap_or(&default);
+
+        let separator = messages.text(group_id, "separator", &Vars::new());
+        let none = messages.text(group_id, "none", &Vars::new());
+        let users: Vec<_> = exemption.users.iter().map(|v| v.to_string()).collect();
+        let vars = Vars::new()
+            .set(
+                "users",
+                if users.is_empty() {
+                    none.clone()
+                } else {
+                    users.join(&separator)
+                },
+            )
+            .set(
+                "roles",
+                if exemption.roles.is_empty() {
+                    none
+                } else {
+                    exemption.roles.join(&separator)
+                },
+            );
+        messages.text(group_id, "exempt_list", &vars)
+    }
+}
+
+fn parse_role(arg: &str) -> Option<&'static str> {
+    match arg {
+        "owner" | "群主" => Some("owner"),
+        "admin" | "管理员" => Some("admin"),
+        _ => None,
+    }
+}
@@ -0,0 +1,250 @@
+use image::ImageFormat;
+use kovi::log::{error, info};
+use kovi::serde_json::Value;
+use kovi::{tokio, Message, RuntimeBot};
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+use crate::download::{DownloadError, Downloader};
+use crate::video::keyframes;
+
+/// 从消息中取出的一张图片
+pub(crate) struct FetchedImage {
+    pub(crate) data: Vec<u8>,
+    /// 所在的顶层消息段序号，合并转发内的图片为该转发消息段的序号
+    pub(crate) segment_index: usize,
+    /// 在合并转发中的位置，顶层图片为 None
+    pub(crate) node: Option<String>,
+}
+
+struct Pending {
+    segment_index: usize,
+    node: Option<String>,
+    depth: usize,
+    type_: String,
+    data: Value,
+}
+
+/// 取出消息中的所有图片、商城表情与视频帧，合并转发按 `forward_max_depth` 递归展开
+///
+/// 最多取出 `max_images` 与配置中 `max_images` 较小者张图片。
+pub(crate) async fn extract_images(
+    bot: &RuntimeBot,
+    downloader: &Downloader,
+    message: &Message,
+    max_images: usize,
+) -> Vec<FetchedImage> {
+    let options = downloader.options();
+    let max_images = max_images.min(options.max_images);
+    let mut pending: VecDeque<_> = message
+        .iter()
+        .enumerate()
+        .map(|(segment_index, segment)| Pending {
+            segment_index,
+            node: None,
+            depth: 0,
+            type_: segment.type_.clone(),
+            data: segment.data.clone(),
+        })
+        .collect();
+
+    let mut imgs = Vec::new();
+    while let Some(item) = pending.pop_front() {
+        match item.type_.as_str() {
+            "image" | "mface" | "marketface" if imgs.len() >= max_images => {
+                info!("单条消息图片超过 {} 张，其余不再检测", max_images);
+                break;
+            }
+            "image" | "mface" | "marketface" => {
+                let fetched = if item.type_ == "image" {
+                    downloader.fetch(bot, &item.data).await
+                } else {
+                    match sticker_url(&item.data) {
+                        Some(url) => downloader.download(&url).await,
+                        None => Err(DownloadError::NoSource),
+                    }
+                };
+                match fetched {
+                    Ok((data, _)) => imgs.push(FetchedImage {
+                        data,
+                        segment_index: item.segment_index,
+                        node: item.node,
+                    }),
+                    Err(err) => error!("获取图片失败: {}, 消息段: {}", err, item.data),
+                }
+            }
+            "video" if options.is_scan_video => {
+                let prefix = match &item.node {
+                    Some(parent) => format!("{} > ", parent),
+                    None => String::new(),
+                };
+                for (label, data) in video_frames(downloader, &item.data).await {
+                    if imgs.len() >= max_images {
+                        break;
+                    }
+                    imgs.push(FetchedImage {
+                        data,
+                        segment_index: item.segment_index,
+                        node: Some(format!("{}{}", prefix, label)),
+                    });
+                }
+            }
+            "forward" if item.depth < options.forward_max_depth => {
+                let nodes = forward_nodes(bot, &item.data).await;
+                for (i, (nickname, content)) in nodes.into_iter().enumerate() {
+                    let node = match &item.node {
+                        Some(parent) => format!("{} > 第 {} 条（{}）", parent, i + 1, nickname),
+                        None => format!("合并转发第 {} 条（{}）", i + 1, nickname),
+                    };
+                    for segment in content {
+                        pending.push_back(Pending {
+                            segment_index: item.segment_index,
+                            node: Some(node.clone()),
+                            depth: item.depth + 1,
+                            type_: segment["type"].as_str().unwrap_or_default().to_string(),
+                            data: segment["data"].clone(),
+                        });
+                    }
+                }
+            }
+            "forward" => info!(
+                "合并转发嵌套超过 {} 层，不再展开",
+                options.forward_max_depth
+            ),
+            _ => {}
+        }
+    }
+    imgs
+}
+
+/// 消息中是否有需要下载检测的消息段
+pub(crate) fn has_media(message: &Message) -> bool {
+    message.iter().any(|segment| {
+        matches!(
+            segment.type_.as_str(),
+            "image" | "mface" | "marketface" | "forward" | "video"
+        )
+    })
+}
+
+/// 视频的关键帧，无法解码时退回封面，返回位置说明与 PNG 数据
+async fn video_frames(downloader: &Downloader, data: &Value) -> Vec<(String, Vec<u8>)> {
+    // 未启用 video 功能时无法解码，不下载视频，直接检测封面
+    let url = data
+        .get("url")
+        .and_then(Value::as_str)
+        .filter(|_| cfg!(feature = "video"));
+    if let Some(url) = url {
+        match decode_video(downloader, url).await {
+            Ok(frames) if !frames.is_empty() => return frames,
+            Ok(_) => info!("视频中没有关键帧，检测封面"),
+            Err(err) => info!("视频无法解码，检测封面: {}", err),
+        }
+    }
+
+    let cover = data
+        .get("cover")
+        .or_else(|| data.get("thumb"))
+        .and_then(Value::as_str)
+        .filter(|v| !v.is_empty());
+    let Some(cover) = cover else {
+        return Vec::new();
+    };
+    match downloader.download(cover).await {
+        Ok((cover, _)) => vec![("视频封面".to_string(), cover)],
+        Err(err) => {
+            error!("获取视频封面失败: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+async fn decode_video(
+    downloader: &Downloader,
+    url: &str,
+) -> Result<Vec<(String, Vec<u8>)>, Box<dyn std::error::Error + Send + Sync>> {
+    let video = downloader.download_video(url).await?;
+    let max_frames = downloader.options().video_frames;
+    // 与图片一样限制像素数，避免解码超大分辨率的视频
+    let max_pixels = downloader.options().max_pixels;
+    let frames =
+        tokio::task::spawn_blocking(move || keyframes(&video, max_frames, max_pixels)).await??;
+
+    let mut encoded = Vec::new();
+    for (i, frame) in frames.into_iter().enumerate() {
+        let mut buf = Vec::new();
+        frame.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
+        encoded.push((format!("视频第 {} 个关键帧", i + 1), buf));
+    }
+    Ok(encoded)
+}
+
+/// 商城表情的图片地址，消息段没有 `url` 时按表情 id 拼出
+fn sticker_url(data: &Value) -> Option<String> {
+    if let Some(url) = data
+        .get("url")
+        .and_then(Value::as_str)
+        .filter(|v| !v.is_empty())
+    {
+        return Some(url.to_string());
+    }
+
+    let emoji_id = data
+        .get("emoji_id")
+        .or_else(|| data.get("face_id"))
+        .and_then(Value::as_str)
+        .filter(|v| v.len() > 2 && v.is_ascii())?;
+    Some(format!(
+        "https://gxh.vip.qq.com/club/item/parcel/item/{}/{}/raw300.gif",
+        &emoji_id[..2],
+        emoji_id
+    ))
+}
+
+/// 合并转发中每个节点的发送者昵称与消息段
+///
+/// 部分 OneBot 实现直接在消息段里带上 `content`，否则通过 `get_forward_msg` 获取。
+async fn forward_nodes(bot: &RuntimeBot, data: &Value) -> Vec<(String, Vec<Value>)> {
+    let nodes = match data.get("content").and_then(Value::as_array) {
+        Some(content) => content.clone(),
+        None => {
+            let Some(id) = data.get("id").and_then(Value::as_str) else {
+                return Vec::new();
+            };
+            match bot.get_forward_msg(id).await {
+                Ok(ret) => ret
+                    .data
+                    .get("messages")
+                    .or_else(|| ret.data.get("message"))
+                    .and_then(Value::as_array)
+                    .cloned()
+                    .unwrap_or_default(),
+                Err(err) => {
+                    error!("获取合并转发失败: {}", err);
+                    return Vec::new();
+                }
+            }
+        }
+    };
+
+    nodes
+        .iter()
+        .map(|node| {
+            // 兼容 `{type: node, data: {...}}` 与 `{sender, message}` 两种格式
+            let inner = node.get("data").unwrap_or(node);
+            let nickname = inner
+                .get("nickname")
+                .or_else(|| inner.get("sender").and_then(|v| v.get("nickname")))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let content = inner
+                .get("content")
+                .or_else(|| inner.get("message"))
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            (nickname, content)
+        })
+        .collect()
+}
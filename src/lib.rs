@@ -1,13 +1,45 @@
-use detector::{download_img, Detector};
+use detector::{process_images, Detector};
+use download::{DownloadOptions, Downloader};
+use exempt::Exemptions;
+use extract::{extract_images, has_media};
 use kovi::bot::runtimebot::kovi_api::KoviApi as _;
-use kovi::log::error;
+use kovi::log::info;
 use kovi::utils::{load_json_data, save_json_data};
-use kovi::{tokio, AllMsgEvent, PluginBuilder as p};
+use kovi::{AllMsgEvent, PluginBuilder as p};
+use locale::{LocaleOptions, Messages};
+use notify::{Notifier, NotifyOptions, NotifyTarget};
+use permission::{PermissionOptions, Permissions};
+use ratelimit::{RateLimitOptions, RateLimiter};
+use schedule::ScheduleOptions;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, RwLock};
+use template::Template;
+use tmp::{TmpManager, TmpOptions};
 
 mod detector;
+mod download;
+mod engine;
+mod exempt;
+mod extract;
+mod font;
+mod locale;
+mod notify;
+mod permission;
+mod pool;
+mod postprocess;
+mod ratelimit;
+mod render;
+mod schedule;
+mod template;
+mod tmp;
+mod video;
+
+pub use engine::{DetectError, Engine, EngineOptions};
+pub use postprocess::{BoundingBox, Detection, DetectionReport, PostprocessOptions};
+pub use render::{
+    draw_detections, encode_image, render_report, OutputFormat, Redaction, RenderOptions,
+};
 
 pub const LONG_MODEL: &[u8] = include_bytes!("../model/long.onnx");
 pub const NAILONG_MODEL: &[u8] = include_bytes!("../model/nailong.onnx");
@@ -35,17 +67,55 @@ impl UserInfo {
 struct Config {
     trigger: f32,
     start_cmd: String,
-    start_msg: String,
+    start_msg: Template,
     stop_cmd: String,
-    stop_msg: String,
+    stop_msg: Template,
     reply_output_img_cmd: String,
-    reply_msg: String,
+    reply_msg: Template,
     my_times_cmd: String,
     is_reply_trigger: bool,
     is_delete_message: bool,
     ban_cooldown: u64,
     ban_duration: usize,
-    ban_msg: String,
+    ban_msg: Template,
+    // 撤回原消息后，以违规区域打码的图片代为重发
+    #[serde(default)]
+    is_repost_redacted: bool,
+    #[serde(default = "default_repost_redaction")]
+    repost_redaction: Redaction,
+    // 重发消息的开头，`{nickname}`、`{user_id}` 替换为发送者
+    #[serde(default = "default_repost_msg")]
+    repost_msg: Template,
+    // 观察模式命令，为空时按检测器使用默认命令
+    #[serde(default)]
+    observe_cmd: String,
+    #[serde(default = "default_observe_msg")]
+    observe_msg: Template,
+    // 旧版观察模式的记录群，启动时并入 `notify_config.json` 的 `observe_targets`
+    #[serde(default, skip_serializing)]
+    observe_report_group: Option<i64>,
+    // 图片以 base64 内联发送，不经过 tmp 目录
+    #[serde(default)]
+    is_send_base64: bool,
+    // 按时间自动开启、关闭戒严
+    #[serde(default)]
+    schedule: ScheduleOptions,
+    #[serde(flatten)]
+    engine: EngineOptions,
+    #[serde(flatten)]
+    render: RenderOptions,
+}
+
+fn default_repost_redaction() -> Redaction {
+    Redaction::Mosaic
+}
+
+fn default_repost_msg() -> Template {
+    "{nickname}({user_id}) 发送了：".into()
+}
+
+fn default_observe_msg() -> Template {
+    "📢📢📢，本群已开启观察模式，只记录不处理".into()
 }
 
 #[kovi::plugin]
@@ -77,21 +147,57 @@ async fn main() {
         load_json_data(HashMap::new(), nailong_user_info_path.as_ref()).unwrap(),
     ));
 
+    // 龙图观察模式
+    let long_observe_path = data_path.join("long_observe.json");
+    let long_observe = load_json_data(HashMap::<i64, bool>::new(), &long_observe_path).unwrap();
+    let long_observe = Arc::new(RwLock::new(long_observe));
+
+    // 奶龙观察模式
+    let nailong_observe_path = data_path.join("nailong_observe.json");
+    let nailong_observe =
+        load_json_data(HashMap::<i64, bool>::new(), &nailong_observe_path).unwrap();
+    let nailong_observe = Arc::new(RwLock::new(nailong_observe));
+
+    // 观察模式的用户信息，与正式次数分开
+    let long_observe_user_info_path = Arc::new(data_path.join("long_observe_user_info.json"));
+    let long_observe_user_info: Arc<Mutex<HashMap<i64, UserInfo>>> = Arc::new(Mutex::new(
+        load_json_data(HashMap::new(), long_observe_user_info_path.as_ref()).unwrap(),
+    ));
+    let nailong_observe_user_info_path = Arc::new(data_path.join("nailong_observe_user_info.json"));
+    let nailong_observe_user_info: Arc<Mutex<HashMap<i64, UserInfo>>> = Arc::new(Mutex::new(
+        load_json_data(HashMap::new(), nailong_observe_user_info_path.as_ref()).unwrap(),
+    ));
+
+    // 豁免名单
+    let exemptions_path = Arc::new(data_path.join("exemptions.json"));
+    let exemptions =
+        Exemptions::new(load_json_data(HashMap::new(), exemptions_path.as_ref()).unwrap());
+
     // 龙图检测器配置
     let long_config = Config {
         trigger: 0.78,
         start_cmd: ".lostart".to_string(),
         stop_cmd: ".lostop".to_string(),
-        start_msg: "📢📢📢，喜欢发龙图的小朋友你们好啊，本群已开启龙图戒严".to_string(),
-        stop_msg: "📢📢📢，本群已关闭龙图戒严".to_string(),
+        start_msg: "📢📢📢，喜欢发龙图的小朋友你们好啊，本群已开启龙图戒严".into(),
+        stop_msg: "📢📢📢，本群已关闭龙图戒严".into(),
         reply_output_img_cmd: "检测".to_string(),
-        reply_msg: "不准发龙图哦，再发打你👊".to_string(),
+        reply_msg: "不准发龙图哦，再发打你👊".into(),
         my_times_cmd: "我的龙图".to_string(),
         is_reply_trigger: true,
         is_delete_message: true,
         ban_cooldown: 60,
         ban_duration: 60,
-        ban_msg: "发发发发发，不准发了👊👊👊".to_string(),
+        ban_msg: "发发发发发，不准发了👊👊👊".into(),
+        is_repost_redacted: false,
+        repost_redaction: default_repost_redaction(),
+        repost_msg: default_repost_msg(),
+        observe_cmd: ".loobserve".to_string(),
+        observe_msg: default_observe_msg(),
+        observe_report_group: None,
+        is_send_base64: false,
+        schedule: ScheduleOptions::default(),
+        engine: EngineOptions::long(),
+        render: RenderOptions::default(),
     };
 
     // 奶龙检测器配置
@@ -99,21 +205,103 @@ async fn main() {
         trigger: 0.78,
         start_cmd: ".nailostart".to_string(),
         stop_cmd: ".nailostop".to_string(),
-        start_msg: "📢📢📢，喜欢发奶龙的小朋友你们好啊，本群已开启奶龙戒严".to_string(),
-        stop_msg: "📢📢📢，本群已关闭奶龙戒严".to_string(),
+        start_msg: "📢📢📢，喜欢发奶龙的小朋友你们好啊，本群已开启奶龙戒严".into(),
+        stop_msg: "📢📢📢，本群已关闭奶龙戒严".into(),
         reply_output_img_cmd: "检测".to_string(),
-        reply_msg: "不准发奶龙哦，再发打你👊".to_string(),
+        reply_msg: "不准发奶龙哦，再发打你👊".into(),
         my_times_cmd: "我的奶龙".to_string(),
         is_reply_trigger: true,
         is_delete_message: true,
         ban_cooldown: 60,
         ban_duration: 60,
-        ban_msg: "发发发发发，不准发了👊👊👊".to_string(),
+        ban_msg: "发发发发发，不准发了👊👊👊".into(),
+        is_repost_redacted: false,
+        repost_redaction: default_repost_redaction(),
+        repost_msg: default_repost_msg(),
+        observe_cmd: ".nailoobserve".to_string(),
+        observe_msg: default_observe_msg(),
+        observe_report_group: None,
+        is_send_base64: false,
+        schedule: ScheduleOptions::default(),
+        engine: EngineOptions::nailong(),
+        render: RenderOptions::default(),
     };
 
-    let nailong_config =
+    let mut nailong_config =
         load_json_data(nailong_config, data_path.join("nailong_config.json")).unwrap();
-    let long_config = load_json_data(long_config, data_path.join("long_config.json")).unwrap();
+    let mut long_config = load_json_data(long_config, data_path.join("long_config.json")).unwrap();
+
+    // 旧配置文件中没有观察模式命令
+    if long_config.observe_cmd.is_empty() {
+        long_config.observe_cmd = ".loobserve".to_string();
+    }
+    if nailong_config.observe_cmd.is_empty() {
+        nailong_config.observe_cmd = ".nailoobserve".to_string();
+    }
+
+    // 旧配置文件中没有类别配置，使用各检测器的默认类别
+    long_config.engine.migrate_labels(&EngineOptions::long());
+    nailong_config
+        .engine
+        .migrate_labels(&EngineOptions::nailong());
+
+    // 图片下载器，两个检测器共用
+    let download_options = load_json_data(
+        DownloadOptions::default(),
+        data_path.join("download_config.json"),
+    )
+    .unwrap();
+    let downloader = Downloader::new(download_options);
+
+    // 临时文件，两个检测器共用
+    let tmp_options =
+        load_json_data(TmpOptions::default(), data_path.join("tmp_config.json")).unwrap();
+    let tmp_manager = TmpManager::new(data_path.join("tmp"), tmp_options);
+    let janitor = Arc::new(tmp_manager.spawn_janitor());
+
+    // 检测通知，两个检测器共用
+    let mut notify_options = load_json_data(
+        NotifyOptions::default(),
+        data_path.join("notify_config.json"),
+    )
+    .unwrap();
+    for group in [
+        long_config.observe_report_group,
+        nailong_config.observe_report_group,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        let target = NotifyTarget::Group(group);
+        if !notify_options.observe_targets.contains(&target) {
+            notify_options.observe_targets.push(target);
+        }
+    }
+    let notifier = Notifier::new(notify_options);
+
+    // 各群使用的语言与消息文本
+    let locale_options = load_json_data(
+        LocaleOptions::default(),
+        data_path.join("locale_config.json"),
+    )
+    .unwrap();
+    let messages = Messages::load(&data_path.join("locales"), locale_options);
+
+    // 限流与防刷屏，两个检测器共用
+    let rate_limit_options = load_json_data(
+        RateLimitOptions::default(),
+        data_path.join("rate_limit_config.json"),
+    )
+    .unwrap();
+    let limiter = RateLimiter::new(rate_limit_options);
+
+    // 管理命令权限，两个检测器共用
+    let permission_options = load_json_data(
+        PermissionOptions::default(),
+        data_path.join("permission_config.json"),
+    )
+    .unwrap();
+    let permissions = Permissions::new(permission_options, messages.clone());
 
     // 创建检测器实例
     let long_detector = Detector::new(
@@ -121,8 +309,13 @@ async fn main() {
         long_config,
         vec!["loong", "xiong"],
         long_whitelist.clone(),
+        long_observe.clone(),
         long_user_info.clone(),
-        data_path.clone(),
+        long_observe_user_info.clone(),
+        tmp_manager.clone(),
+        notifier.clone(),
+        limiter.clone(),
+        messages.clone(),
         "龙图".to_string(),
     );
 
@@ -131,20 +324,42 @@ async fn main() {
         nailong_config,
         vec!["nailong"],
         nailong_whitelist.clone(),
+        nailong_observe.clone(),
         nailong_user_info.clone(),
-        data_path.clone(),
+        nailong_observe_user_info.clone(),
+        tmp_manager.clone(),
+        notifier.clone(),
+        limiter.clone(),
+        messages.clone(),
         "奶龙".to_string(),
     );
 
+    // 定时戒严
+    let schedules = Arc::new([
+        long_detector.spawn_schedule(bot.clone()),
+        nailong_detector.spawn_schedule(bot.clone()),
+    ]);
+
     let handle_admin = {
         let long_detector = long_detector.clone();
         let nailong_detector = nailong_detector.clone();
+        let bot = bot.clone();
+        let permissions = permissions.clone();
+        let exemptions = exemptions.clone();
         move |e: Arc<AllMsgEvent>| {
             let long_detector = long_detector.clone();
             let nailong_detector = nailong_detector.clone();
+            let bot = bot.clone();
+            let permissions = permissions.clone();
+            let exemptions = exemptions.clone();
             async move {
-                long_detector.handle_admin_command(&e);
-                nailong_detector.handle_admin_command(&e);
+                exemptions.handle_command(&e, &bot, &permissions).await;
+                long_detector
+                    .handle_admin_command(&e, &bot, &permissions)
+                    .await;
+                nailong_detector
+                    .handle_admin_command(&e, &bot, &permissions)
+                    .await;
             }
         }
     };
@@ -162,14 +377,43 @@ async fn main() {
         }
     };
 
+    let handle_status = {
+        let long_detector = long_detector.clone();
+        let nailong_detector = nailong_detector.clone();
+        let permissions = permissions.clone();
+        move |e: Arc<AllMsgEvent>| {
+            let long_detector = long_detector.clone();
+            let nailong_detector = nailong_detector.clone();
+            let permissions = permissions.clone();
+            async move {
+                let Some(group_id) = e.group_id else {
+                    return;
+                };
+                if e.borrow_text().map(str::trim) != Some(permissions.options().status_cmd.as_str())
+                {
+                    return;
+                }
+                e.reply(format!(
+                    "{}\n\n{}",
+                    long_detector.status(group_id),
+                    nailong_detector.status(group_id)
+                ));
+            }
+        }
+    };
+
     let handle_check = {
         let long_detector = long_detector.clone();
         let nailong_detector = nailong_detector.clone();
         let bot = bot.clone();
+        let downloader = downloader.clone();
+        let limiter = limiter.clone();
         move |e: Arc<AllMsgEvent>| {
             let long_detector = long_detector.clone();
             let nailong_detector = nailong_detector.clone();
             let bot = bot.clone();
+            let downloader = downloader.clone();
+            let limiter = limiter.clone();
             async move {
                 // 首先检查群号是否在白名单中
                 let group_id = match e.group_id {
@@ -181,18 +425,8 @@ async fn main() {
                 let is_in_long_whitelist;
                 let is_in_nailong_whitelist;
                 {
-                    is_in_long_whitelist = *long_detector
-                        .whitelist
-                        .read()
-                        .unwrap()
-                        .get(&group_id)
-                        .unwrap_or(&false);
-                    is_in_nailong_whitelist = *nailong_detector
-                        .whitelist
-                        .read()
-                        .unwrap()
-                        .get(&group_id)
-                        .unwrap_or(&false);
+                    is_in_long_whitelist = long_detector.is_enabled(group_id);
+                    is_in_nailong_whitelist = nailong_detector.is_enabled(group_id);
 
                     //如果都不在白名单中，直接返回
                     if !is_in_long_whitelist && !is_in_nailong_whitelist {
@@ -211,41 +445,30 @@ async fn main() {
                     }
                 }
 
-                let imgs = e.message.get("image");
-                if imgs.is_empty() {
+                if !has_media(&e.message) {
                     return;
                 }
 
-                let urls: Vec<_> = imgs
-                    .iter()
-                    .map(|x| x.data.get("url").unwrap().as_str().unwrap())
-                    .collect();
-
-                let mut imgs_data = Vec::new();
-                for url in &urls {
-                    match download_img(url).await {
-                        Ok((data, format)) => imgs_data.push((data, format)),
-                        Err(err) => {
-                            error!("下载图片失败: {}", err);
-                            continue;
-                        }
-                    }
+                // 超出频率限制的图片不下载、不检测，下载前先预留额度
+                let reserved =
+                    limiter.try_reserve(group_id, e.user_id, downloader.options().max_images);
+                if reserved == 0 {
+                    info!("群 {} 的 {} 检测过于频繁，跳过", group_id, e.user_id);
+                    return;
                 }
-
+                let imgs_data = extract_images(&bot, &downloader, &e.message, reserved).await;
+                limiter.refund(group_id, e.user_id, reserved - imgs_data.len());
                 if imgs_data.is_empty() {
                     return;
                 }
-                if is_in_long_whitelist {
-                    long_detector
-                        .process_images(&e, &bot, &imgs_data, true)
-                        .await;
-                }
-
-                if is_in_nailong_whitelist {
-                    nailong_detector
-                        .process_images(&e, &bot, &imgs_data, true)
-                        .await;
-                }
+                let detectors: Vec<_> = [
+                    (&long_detector, is_in_long_whitelist),
+                    (&nailong_detector, is_in_nailong_whitelist),
+                ]
+                .into_iter()
+                .filter_map(|(detector, is_enabled)| is_enabled.then_some(detector))
+                .collect();
+                process_images(&detectors, &e, &bot, &imgs_data, true).await;
             }
         }
     };
@@ -254,10 +477,16 @@ async fn main() {
         let long_detector = long_detector.clone();
         let nailong_detector = nailong_detector.clone();
         let bot = bot.clone();
+        let downloader = downloader.clone();
+        let limiter = limiter.clone();
+        let exemptions = exemptions.clone();
         move |e: Arc<AllMsgEvent>| {
             let long_detector = long_detector.clone();
             let nailong_detector = nailong_detector.clone();
             let bot = bot.clone();
+            let downloader = downloader.clone();
+            let limiter = limiter.clone();
+            let exemptions = exemptions.clone();
             async move {
                 // 首先检查群号是否在白名单中
                 let group_id = match e.group_id {
@@ -269,18 +498,8 @@ async fn main() {
                 let is_in_long_whitelist;
                 let is_in_nailong_whitelist;
                 {
-                    is_in_long_whitelist = *long_detector
-                        .whitelist
-                        .read()
-                        .unwrap()
-                        .get(&group_id)
-                        .unwrap_or(&false);
-                    is_in_nailong_whitelist = *nailong_detector
-                        .whitelist
-                        .read()
-                        .unwrap()
-                        .get(&group_id)
-                        .unwrap_or(&false);
+                    is_in_long_whitelist = long_detector.is_enabled(group_id);
+                    is_in_nailong_whitelist = nailong_detector.is_enabled(group_id);
 
                     //如果都不在白名单中，直接返回
                     if !is_in_long_whitelist && !is_in_nailong_whitelist {
@@ -296,49 +515,44 @@ async fn main() {
                     }
                 }
 
-                let imgs = e.message.get("image");
-                if imgs.is_empty() {
+                // 豁免的成员不计数、不禁言、不撤回
+                if exemptions.is_exempt(&e) {
                     return;
                 }
 
-                let urls: Vec<_> = imgs
-                    .iter()
-                    .map(|x| x.data.get("url").unwrap().as_str().unwrap())
-                    .collect();
-
-                let mut imgs_data = Vec::new();
-                for url in &urls {
-                    match download_img(url).await {
-                        Ok((data, format)) => imgs_data.push((data, format)),
-                        Err(err) => {
-                            error!("下载图片失败: {}", err);
-                            continue;
-                        }
-                    }
+                if !has_media(&e.message) {
+                    return;
                 }
 
-                if imgs_data.is_empty() {
+                // 超出频率限制的图片不下载、不检测，下载前先预留额度
+                let reserved =
+                    limiter.try_reserve(group_id, e.user_id, downloader.options().max_images);
+                if reserved == 0 {
+                    info!("群 {} 的 {} 检测过于频繁，跳过", group_id, e.user_id);
                     return;
                 }
-
-                if is_in_long_whitelist {
-                    long_detector
-                        .process_images(&e, &bot, &imgs_data, false)
-                        .await;
+                let imgs_data = extract_images(&bot, &downloader, &e.message, reserved).await;
+                limiter.refund(group_id, e.user_id, reserved - imgs_data.len());
+                if imgs_data.is_empty() {
+                    return;
                 }
 
-                if is_in_nailong_whitelist {
-                    nailong_detector
-                        .process_images(&e, &bot, &imgs_data, false)
-                        .await;
-                }
+                let detectors: Vec<_> = [
+                    (&long_detector, is_in_long_whitelist),
+                    (&nailong_detector, is_in_nailong_whitelist),
+                ]
+                .into_iter()
+                .filter_map(|(detector, is_enabled)| is_enabled.then_some(detector))
+                .collect();
+                process_images(&detectors, &e, &bot, &imgs_data, false).await;
             }
         }
     };
 
     // 注册处理器
-    p::on_admin_msg(handle_admin);
+    p::on_group_msg(handle_admin);
     p::on_group_msg(handle_my_times);
+    p::on_group_msg(handle_status);
     p::on_group_msg(handle_check);
     p::on_group_msg(handle_normal);
 
@@ -347,21 +561,43 @@ async fn main() {
         let nailong_whitelist = nailong_whitelist.clone();
         let long_whitelist_path = Arc::new(long_whitelist_path);
         let nailong_whitelist_path = Arc::new(nailong_whitelist_path);
-        let data_path = data_path.clone();
+        let tmp_manager = tmp_manager.clone();
+        let janitor = janitor.clone();
+        let schedules = schedules.clone();
         let long_user_info = long_user_info.clone();
         let nailong_user_info = nailong_user_info.clone();
         let long_user_info_path = long_user_info_path.clone();
         let nailong_user_info_path = nailong_user_info_path.clone();
+        let exemptions = exemptions.clone();
+        let exemptions_path = exemptions_path.clone();
+        let long_observe = long_observe.clone();
+        let nailong_observe = nailong_observe.clone();
+        let long_observe_path = Arc::new(long_observe_path);
+        let nailong_observe_path = Arc::new(nailong_observe_path);
+        let long_observe_user_info = long_observe_user_info.clone();
+        let nailong_observe_user_info = nailong_observe_user_info.clone();
         move || {
             let long_whitelist = long_whitelist.clone();
             let nailong_whitelist = nailong_whitelist.clone();
             let long_whitelist_path = long_whitelist_path.clone();
             let nailong_whitelist_path = nailong_whitelist_path.clone();
-            let data_path = data_path.clone();
+            let tmp_manager = tmp_manager.clone();
+            let janitor = janitor.clone();
+            let schedules = schedules.clone();
             let long_user_info = long_user_info.clone();
             let nailong_user_info = nailong_user_info.clone();
             let long_user_info_path = long_user_info_path.clone();
             let nailong_user_info_path = nailong_user_info_path.clone();
+            let exemptions = exemptions.clone();
+            let exemptions_path = exemptions_path.clone();
+            let long_observe = long_observe.clone();
+            let nailong_observe = nailong_observe.clone();
+            let long_observe_path = long_observe_path.clone();
+            let nailong_observe_path = nailong_observe_path.clone();
+            let long_observe_user_info = long_observe_user_info.clone();
+            let nailong_observe_user_info = nailong_observe_user_info.clone();
+            let long_observe_user_info_path = long_observe_user_info_path.clone();
+            let nailong_observe_user_info_path = nailong_observe_user_info_path.clone();
             async move {
                 {
                     let long_whitelist = long_whitelist.write().unwrap();
@@ -383,18 +619,44 @@ async fn main() {
                     save_json_data(&*nailong_user_info, nailong_user_info_path.as_ref()).unwrap();
                 }
 
-                let tmp_dir = data_path.join("tmp");
-                if let Ok(mut entries) = tokio::fs::read_dir(&tmp_dir).await {
-                    while let Some(entry) = entries.next_entry().await.unwrap_or(None) {
-                        if let Ok(file_type) = entry.file_type().await {
-                            if file_type.is_file() {
-                                if let Err(e) = tokio::fs::remove_file(entry.path()).await {
-                                    error!("Failed to delete file {:?}: {}", entry.path(), e);
-                                }
-                            }
-                        }
-                    }
+                {
+                    let long_observe = long_observe.read().unwrap();
+                    save_json_data(&*long_observe, long_observe_path.as_ref()).unwrap();
+                }
+
+                {
+                    let nailong_observe = nailong_observe.read().unwrap();
+                    save_json_data(&*nailong_observe, nailong_observe_path.as_ref()).unwrap();
+                }
+
+                {
+                    let long_observe_user_info = long_observe_user_info.lock().unwrap();
+                    save_json_data(
+                        &*long_observe_user_info,
+                        long_observe_user_info_path.as_ref(),
+                    )
+                    .unwrap();
+                }
+
+                {
+                    let nailong_observe_user_info = nailong_observe_user_info.lock().unwrap();
+                    save_json_data(
+                        &*nailong_observe_user_info,
+                        nailong_observe_user_info_path.as_ref(),
+                    )
+                    .unwrap();
+                }
+
+                {
+                    let groups = exemptions.groups.read().unwrap();
+                    save_json_data(&*groups, exemptions_path.as_ref()).unwrap();
+                }
+
+                for schedule in schedules.iter() {
+                    schedule.abort();
                 }
+                janitor.abort();
+                tmp_manager.clear().await;
             }
         }
     });
@@ -0,0 +1,267 @@
+use kovi::log::{error, info};
+use kovi::serde_json;
+use kovi::utils::load_json_data;
+use kovi::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::template::{Template, Vars};
+
+/// 内置语言，其他语言需要在 `locales` 目录中补全所有条目
+const ZH_CN: &str = "zh-CN";
+const EN: &str = "en";
+
+/// 语言配置，保存在 `locale_config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LocaleOptions {
+    /// 默认语言，使用默认语言的群优先使用检测器配置中的消息
+    pub(crate) default_locale: String,
+    /// 各群使用的语言，键为群号
+    pub(crate) groups: HashMap<i64, String>,
+}
+
+impl Default for LocaleOptions {
+    fn default() -> Self {
+        Self {
+            default_locale: ZH_CN.to_string(),
+            groups: HashMap::new(),
+        }
+    }
+}
+
+/// 按群选择语言的消息文本
+#[derive(Clone)]
+pub(crate) struct Messages {
+    options: Arc<LocaleOptions>,
+    locales: Arc<HashMap<String, HashMap<String, Template>>>,
+}
+
+static EMPTY: Template = Template::One(String::new());
+
+impl Messages {
+    /// 读取 `dir` 下的 `<语言>.json`，内置语言的文件不存在时写入内置文本，缺少的条目使用内置文本
+    pub(crate) fn load(dir: &Path, options: LocaleOptions) -> Self {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            error!("创建语言目录失败: {}", err);
+        }
+
+        let mut locales = HashMap::new();
+        for (locale, entries) in [(ZH_CN, ZH_CN_MESSAGES), (EN, EN_MESSAGES)] {
+            let mut builtin = to_map(entries);
+            match load_json_data(builtin.clone(), dir.join(format!("{}.json", locale))) {
+                Ok(loaded) => builtin.extend(loaded),
+                Err(err) => error!("读取语言文件 {} 失败: {}", locale, err),
+            }
+            locales.insert(locale.to_string(), builtin);
+        }
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(v) => v,
+            Err(err) => {
+                error!("读取语言目录失败: {}", err);
+                return Self::new(options, locales);
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|v| v != "json") {
+                continue;
+            }
+            let Some(locale) = path.file_stem().and_then(|v| v.to_str()) else {
+                continue;
+            };
+            if locales.contains_key(locale) {
+                continue;
+            }
+            let loaded = std::fs::read(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|data| serde_json::from_slice(&data).map_err(|err| err.to_string()));
+            match loaded {
+                Ok(messages) => {
+                    info!("已加载语言: {}", locale);
+                    locales.insert(locale.to_string(), messages);
+                }
+                Err(err) => error!("读取语言文件 {} 失败: {}", locale, err),
+            }
+        }
+
+        Self::new(options, locales)
+    }
+
+    fn new(options: LocaleOptions, locales: HashMap<String, HashMap<String, Template>>) -> Self {
+        for locale in options.groups.values() {
+            if !locales.contains_key(locale) {
+                error!("未找到语言 {}，使用默认语言", locale);
+            }
+        }
+        Self {
+            options: Arc::new(options),
+            locales: Arc::new(locales),
+        }
+    }
+
+    fn locale(&self, group_id: i64) -> &str {
+        self.options
+            .groups
+            .get(&group_id)
+            .unwrap_or(&self.options.default_locale)
+    }
+
+    /// 依次查找本群语言、默认语言与内置中文
+    pub(crate) fn get(&self, group_id: i64, key: &str) -> &Template {
+        [self.locale(group_id), &self.options.default_locale, ZH_CN]
+            .into_iter()
+            .find_map(|locale| self.locales.get(locale)?.get(key))
+            .unwrap_or(&EMPTY)
+    }
+
+    pub(crate) fn text(&self, group_id: i64, key: &str, vars: &Vars) -> String {
+        self.get(group_id, key).render(vars)
+    }
+
+    pub(crate) fn message(&self, group_id: i64, key: &str, vars: &Vars) -> Message {
+        self.get(group_id, key).message(vars)
+    }
+
+    /// 可在配置文件中自定义的消息：默认语言的群使用配置，其他语言的群使用语言文件
+    pub(crate) fn configured<'a>(
+        &'a self,
+        group_id: i64,
+        key: &str,
+        configured: &'a Template,
+    ) -> &'a Template {
+        if self.locale(group_id) == self.options.default_locale {
+            configured
+        } else {
+            self.get(group_id, key)
+        }
+    }
+
+    /// 检测器在本群语言中的名称，对应条目 `name.<检测器名>`
+    pub(crate) fn detector_name(&self, group_id: i64, name: &str) -> String {
+        let template = self.get(group_id, &format!("name.{}", name));
+        if template.is_empty() {
+            name.to_string()
+        } else {
+            template.render(&Vars::new())
+        }
+    }
+}
+
+fn to_map(entries: &[(&str, &str)]) -> HashMap<String, Template> {
+    entries
+        .iter()
+        .map(|(key, text)| (key.to_string(), Template::from(*text)))
+        .collect()
+}
+
+const ZH_CN_MESSAGES: &[(&str, &str)] = &[
+    ("name.龙图", "龙图"),
+    ("name.奶龙", "奶龙"),
+    ("reply_msg", "不准发{detector}哦，再发打你👊"),
+    ("ban_msg", "发发发发发，不准发了👊👊👊"),
+    (
+        "start_msg",
+        "📢📢📢，喜欢发{detector}的小朋友你们好啊，本群已开启{detector}戒严",
+    ),
+    ("stop_msg", "📢📢📢，本群已关闭{detector}戒严"),
+    ("observe_msg", "📢📢📢，本群已开启观察模式，只记录不处理"),
+    ("repost_msg", "{nickname}({user_id}) 发送了："),
+    ("raid_msg", "检测到刷屏，禁言处理👊"),
+    ("denied_msg", "你没有权限使用这个命令哦"),
+    (
+        "my_times",
+        "你在本群发送{detector}的次数为: {count}\n你的总发送次数为: {total}",
+    ),
+    ("my_times_none", "你还没有发送过{detector}哦~"),
+    ("reply_summary", "共 {total} 张图片，{violations} 张违规"),
+    ("image_index", "第 {index} 张"),
+    ("image_label", "：{label}"),
+    ("image_frame", "，第 {frame} 帧"),
+    ("image_score", "，相似度：{prob}"),
+    ("image_node", "位置：{node}"),
+    ("image_render_failed", "标注图片生成失败"),
+    ("image_failed", "第 {index} 张：检测失败（{error}）"),
+    ("detect_failed", "{detector}检测失败"),
+    (
+        "status",
+        "{detector}：{state}\n判定阈值：{trigger}，候选框阈值：{conf}，违规类别：{labels}\n禁言：{ban_cooldown} 秒内再次发送禁言 {ban_seconds} 秒\n今日检测：{today} 次\n模型版本：{version}",
+    ),
+    ("state_enforced", "戒严中"),
+    ("state_observing", "观察中"),
+    ("state_disabled", "未开启"),
+    ("exempt_list", "本群豁免成员: {users}\n本群豁免身份: {roles}"),
+    ("exempt_bad_arg", "无法识别的参数: {arg}"),
+    ("exempt_usage", "用法: {cmd} @成员 / QQ号 / owner / admin"),
+    ("none", "无"),
+    (
+        "detection_report",
+        "群 {group_id} 的 {user}({user_id}) 发送了{detector}\n相似度：{scores}\n处理：{actions}",
+    ),
+    ("observe_prefix", "[观察] "),
+    ("action_reply", "回复"),
+    ("action_repost", "打码重发"),
+    ("action_delete", "撤回"),
+    ("action_ban", "禁言 {ban_seconds} 秒"),
+    ("separator", "、"),
+];
+
+const EN_MESSAGES: &[(&str, &str)] = &[
+    ("name.龙图", "loong"),
+    ("name.奶龙", "nailong"),
+    ("reply_msg", "No {detector} pictures here, or else 👊"),
+    (
+        "ban_msg",
+        "{at} Stop posting {detector} pictures! Muted for {ban_minutes} min 👊",
+    ),
+    (
+        "start_msg",
+        "📢 {detector} pictures are now banned in this group",
+    ),
+    ("stop_msg", "📢 {detector} pictures are allowed again"),
+    (
+        "observe_msg",
+        "📢 Observe mode is on: detections are recorded but not enforced",
+    ),
+    ("repost_msg", "{nickname}({user_id}) sent:"),
+    ("raid_msg", "{at} Flood detected, muted for {ban_minutes} min 👊"),
+    ("denied_msg", "You are not allowed to use this command"),
+    (
+        "my_times",
+        "You have sent {detector} pictures {count} times in this group\n{total} times in total",
+    ),
+    ("my_times_none", "You have never sent {detector} pictures~"),
+    ("reply_summary", "{violations} of {total} images violate the rules"),
+    ("image_index", "Image {index}"),
+    ("image_label", ": {label}"),
+    ("image_frame", ", frame {frame}"),
+    ("image_score", ", score: {prob}"),
+    ("image_node", "Location: {node}"),
+    ("image_render_failed", "Failed to render the annotated image"),
+    ("image_failed", "Image {index}: detection failed ({error})"),
+    ("detect_failed", "{detector} detection failed"),
+    (
+        "status",
+        "{detector}: {state}\nTrigger: {trigger}, box threshold: {conf}, labels: {labels}\nBan: {ban_seconds} s when sent again within {ban_cooldown} s\nDetections today: {today}\nModel version: {version}",
+    ),
+    ("state_enforced", "enforced"),
+    ("state_observing", "observing"),
+    ("state_disabled", "off"),
+    ("exempt_list", "Exempt members: {users}\nExempt roles: {roles}"),
+    ("exempt_bad_arg", "Unrecognized argument: {arg}"),
+    ("exempt_usage", "Usage: {cmd} @member / QQ id / owner / admin"),
+    ("none", "none"),
+    (
+        "detection_report",
+        "{user}({user_id}) sent {detector} pictures in group {group_id}\nScores: {scores}\nActions: {actions}",
+    ),
+    ("observe_prefix", "[observe] "),
+    ("action_reply", "reply"),
+    ("action_repost", "redact and repost"),
+    ("action_delete", "delete"),
+    ("action_ban", "mute {ban_seconds} s"),
+    ("separator", ", "),
+];
@@ -0,0 +1,90 @@
+use kovi::{Message, RuntimeBot};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 通知发送的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NotifyTarget {
+    /// 管理群
+    Group(i64),
+    /// 私聊
+    Private(i64),
+}
+
+/// 检测通知配置，保存在 `notify_config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct NotifyOptions {
+    /// 各群的通知位置，键为被监控的群号
+    pub(crate) targets: HashMap<i64, Vec<NotifyTarget>>,
+    /// 没有单独配置的群使用的通知位置
+    pub(crate) default_targets: Vec<NotifyTarget>,
+    /// 只接收观察模式记录的通知位置，所有群共用
+    pub(crate) observe_targets: Vec<NotifyTarget>,
+    /// 是否附带标注后的图片
+    pub(crate) include_image: bool,
+}
+
+impl Default for NotifyOptions {
+    fn default() -> Self {
+        Self {
+            targets: HashMap::new(),
+            default_targets: Vec::new(),
+            observe_targets: Vec::new(),
+            include_image: true,
+        }
+    }
+}
+
+/// 把检测记录发给管理员，便于在主群之外审核
+#[derive(Clone)]
+pub(crate) struct Notifier {
+    options: Arc<NotifyOptions>,
+}
+
+impl Notifier {
+    pub(crate) fn new(options: NotifyOptions) -> Self {
+        Self {
+            options: Arc::new(options),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &NotifyOptions {
+        &self.options
+    }
+
+    /// 本群的通知位置，观察模式的记录还会发到 `observe_targets`，重复的位置只发一次
+    pub(crate) fn targets(&self, group_id: i64, is_observe: bool) -> Vec<NotifyTarget> {
+        let mut targets = self
+            .options
+            .targets
+            .get(&group_id)
+            .unwrap_or(&self.options.default_targets)
+            .clone();
+        if is_observe {
+            for target in &self.options.observe_targets {
+                if !targets.contains(target) {
+                    targets.push(*target);
+                }
+            }
+        }
+        targets
+    }
+
+    pub(crate) fn is_enabled(&self, group_id: i64, is_observe: bool) -> bool {
+        !self.targets(group_id, is_observe).is_empty()
+    }
+
+    pub(crate) fn send(&self, bot: &RuntimeBot, group_id: i64, is_observe: bool, msg: Message) {
+        for target in self.targets(group_id, is_observe) {
+            match target {
+                // 不往被监控的群本身发送
+                NotifyTarget::Group(id) if id == group_id => {}
+                NotifyTarget::Group(id) => bot.send_group_msg(id, msg.clone()),
+                NotifyTarget::Private(id) => bot.send_private_msg(id, msg.clone()),
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use kovi::log::error;
+use kovi::serde_json::Value;
+use kovi::{AllMsgEvent, RuntimeBot};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::locale::Messages;
+use crate::template::{Template, Vars};
+
+/// 管理命令的权限配置，保存在 `permission_config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct PermissionOptions {
+    /// 群主可以使用管理命令
+    pub(crate) allow_group_owner: bool,
+    /// 群管理员可以使用管理命令
+    pub(crate) allow_group_admin: bool,
+    /// 各群委派的协管，键为群号
+    pub(crate) moderators: HashMap<i64, Vec<i64>>,
+    /// 在所有群都可以使用管理命令的协管
+    pub(crate) global_moderators: Vec<i64>,
+    /// 没有权限时的回复，为空时不回复
+    pub(crate) denied_msg: Template,
+    /// 添加豁免
+    pub(crate) exempt_cmd: String,
+    /// 移除豁免
+    pub(crate) unexempt_cmd: String,
+    /// 查看本群豁免名单
+    pub(crate) exempt_list_cmd: String,
+    /// 查看本群检测状态，所有成员可用
+    pub(crate) status_cmd: String,
+}
+
+impl Default for PermissionOptions {
+    fn default() -> Self {
+        Self {
+            allow_group_owner: true,
+            allow_group_admin: true,
+            moderators: HashMap::new(),
+            global_moderators: Vec::new(),
+            denied_msg: "你没有权限使用这个命令哦".into(),
+            exempt_cmd: ".loexempt".to_string(),
+            unexempt_cmd: ".lounexempt".to_string(),
+            exempt_list_cmd: ".loexempts".to_string(),
+            status_cmd: ".lostatus".to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Permissions {
+    options: Arc<PermissionOptions>,
+    messages: Messages,
+}
+
+impl Permissions {
+    pub(crate) fn new(options: PermissionOptions, messages: Messages) -> Self {
+        Self {
+            options: Arc::new(options),
+            messages,
+        }
+    }
+
+    pub(crate) fn options(&self) -> &PermissionOptions {
+        &self.options
+    }
+
+    pub(crate) fn messages(&self) -> &Messages {
+        &self.messages
+    }
+
+    /// 依次检查 bot 管理员、协管、群主与群管理员
+    pub(crate) async fn is_authorized(&self, bot: &RuntimeBot, e: &AllMsgEvent) -> bool {
+        let user_id = e.user_id;
+        if bot
+            .get_all_admin()
+            .is_ok_and(|admins| admins.contains(&user_id))
+        {
+            return true;
+        }
+
+        if self.options.global_moderators.contains(&user_id) {
+            return true;
+        }
+
+        let Some(group_id) = e.group_id else {
+            return false;
+        };
+        if self
+            .options
+            .moderators
+            .get(&group_id)
+            .is_some_and(|v| v.contains(&user_id))
+        {
+            return true;
+        }
+
+        match group_role(bot, e, group_id).await.as_deref() {
+            Some("owner") => self.options.allow_group_owner,
+            Some("admin") => self.options.allow_group_admin,
+            _ => false,
+        }
+    }
+
+    /// 检查权限，没有权限时按配置回复
+    pub(crate) async fn check(&self, bot: &RuntimeBot, e: &AllMsgEvent) -> bool {
+        if self.is_authorized(bot, e).await {
+            return true;
+        }
+        let group_id = e.group_id.unwrap_or_default();
+        let denied_msg = self
+            .messages
+            .configured(group_id, "denied_msg", &self.options.denied_msg);
+        if !denied_msg.is_empty() {
+            e.reply(denied_msg.message(&Vars::new().sender(e)));
+        }
+        false
+    }
+}
+
+/// 通过 `get_group_member_info` 查询群身份，失败时使用消息事件中的身份
+async fn group_role(bot: &RuntimeBot, e: &AllMsgEvent, group_id: i64) -> Option<String> {
+    match bot.get_group_member_info(group_id, e.user_id, true).await {
+        Ok(ret) => {
+            if let Some(role) = ret.data.get("role").and_then(Value::as_str) {
+                return Some(role.to_string());
+            }
+        }
+        Err(err) => error!("获取群成员信息失败: {}", err),
+    }
+    e.sender.role.clone()
+}
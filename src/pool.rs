@@ -0,0 +1,144 @@
+use kovi::tokio::sync::oneshot;
+use ort::session::Session;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+/// 同一模型的会话池，空闲会话按群轮询分配，避免单个群占满推理资源
+pub(crate) struct SessionPool {
+    inner: Arc<Mutex<PoolState>>,
+}
+
+struct PoolState {
+    idle: Vec<Session>,
+    /// 每个群的等待队列
+    waiters: HashMap<i64, VecDeque<oneshot::Sender<Session>>>,
+    /// 有等待者的群，按轮询顺序排列
+    order: VecDeque<i64>,
+}
+
+impl SessionPool {
+    pub(crate) fn new(sessions: Vec<Session>) -> Self {
+        assert!(!sessions.is_empty(), "会话池至少需要一个会话");
+        Self {
+            inner: Arc::new(Mutex::new(PoolState {
+                idle: sessions,
+                waiters: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// 为指定群获取一个会话，没有空闲会话时排队等待
+    pub(crate) async fn acquire(&self, group_id: i64) -> PooledSession {
+        let session = match self.try_acquire(group_id) {
+            Ok(session) => session,
+            Err(rx) => {
+                let mut waiter = Waiter {
+                    rx,
+                    pool: self.inner.clone(),
+                };
+                // 发送端只会在交付会话后被消耗，池存在期间不会被丢弃
+                (&mut waiter.rx).await.expect("会话池已关闭")
+            }
+        };
+        self.wrap(session)
+    }
+
+    /// [`SessionPool::acquire`] 的阻塞版本，不能在异步运行时中调用
+    pub(crate) fn acquire_blocking(&self, group_id: i64) -> PooledSession {
+        let session = match self.try_acquire(group_id) {
+            Ok(session) => session,
+            Err(rx) => rx.blocking_recv().expect("会话池已关闭"),
+        };
+        self.wrap(session)
+    }
+
+    fn try_acquire(&self, group_id: i64) -> Result<Session, oneshot::Receiver<Session>> {
+        let mut state = self.inner.lock().unwrap();
+        if state.order.is_empty() {
+            if let Some(session) = state.idle.pop() {
+                return Ok(session);
+            }
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let queue = state.waiters.entry(group_id).or_default();
+        queue.push_back(tx);
+        if queue.len() == 1 {
+            state.order.push_back(group_id);
+        }
+        Err(rx)
+    }
+
+    fn wrap(&self, session: Session) -> PooledSession {
+        PooledSession {
+            session: Some(session),
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+impl PoolState {
+    fn release(&mut self, mut session: Session) {
+        while let Some(group_id) = self.order.pop_front() {
+            let Some(queue) = self.waiters.get_mut(&group_id) else {
+                continue;
+            };
+
+            let tx = queue.pop_front();
+            if queue.is_empty() {
+                self.waiters.remove(&group_id);
+            } else {
+                self.order.push_back(group_id);
+            }
+
+            match tx.map(|tx| tx.send(session)) {
+                Some(Ok(())) => return,
+                // 等待者已放弃，交给下一个
+                Some(Err(returned)) => session = returned,
+                None => unreachable!("空队列不应留在轮询顺序中"),
+            }
+        }
+
+        self.idle.push(session);
+    }
+}
+
+/// 排队中的等待者，等待被取消时归还已经交付但未取走的会话
+struct Waiter {
+    rx: oneshot::Receiver<Session>,
+    pool: Arc<Mutex<PoolState>>,
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        // 关闭后发送端不会再交付，之前已交付的会话仍可取出
+        self.rx.close();
+        if let Ok(session) = self.rx.try_recv() {
+            self.pool.lock().unwrap().release(session);
+        }
+    }
+}
+
+/// 借出的会话，离开作用域时自动归还
+pub(crate) struct PooledSession {
+    session: Option<Session>,
+    pool: Arc<Mutex<PoolState>>,
+}
+
+impl Deref for PooledSession {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        self.session.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            self.pool.lock().unwrap().release(session);
+        }
+    }
+}
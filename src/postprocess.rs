@@ -0,0 +1,306 @@
+use ndarray::{ArrayView2, Axis};
+use serde::{Deserialize, Serialize};
+
+/// 模型输入尺寸
+pub(crate) const INPUT_SIZE: u32 = 640;
+
+/// 后处理参数，平铺在检测器配置中
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostprocessOptions {
+    /// 候选框最低置信度
+    pub conf_threshold: f32,
+    /// NMS 的 IoU 阈值
+    pub nms_iou_threshold: f32,
+    /// 为 true 时不同类别的框也互相压制，负类别依赖此项压制违规框
+    pub class_agnostic_nms: bool,
+    /// NMS 后最多保留的框数
+    pub max_detections: usize,
+    /// 框面积占原图面积的最小比例
+    pub min_box_area: Option<f32>,
+}
+
+impl Default for PostprocessOptions {
+    fn default() -> Self {
+        Self {
+            conf_threshold: 0.3,
+            nms_iou_threshold: 0.7,
+            class_agnostic_nms: true,
+            max_detections: 300,
+            min_box_area: None,
+        }
+    }
+}
+
+/// 原图坐标系下的框
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoundingBox {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+/// 模型类别在检测器中的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LabelKind {
+    /// 违规类别，计入相似度
+    Violation,
+    /// 负类别，参与 NMS 以压制与其重叠的违规框，自身不计入
+    Negative,
+    /// 忽略类别，直接丢弃
+    Ignored,
+}
+
+/// 经过阈值与 NMS 筛选后的单个违规框
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Detection {
+    pub label: String,
+    pub confidence: f32,
+    pub bbox: BoundingBox,
+    /// 所在帧，静态图片恒为 0
+    pub frame_index: usize,
+}
+
+/// 一张图片的检测结果
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectionReport {
+    /// 检测器名称
+    pub detector: String,
+    /// 相似度，即所有违规框的最高置信度
+    pub score: f32,
+    pub detections: Vec<Detection>,
+    /// 参与检测的帧数
+    pub frames: usize,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl DetectionReport {
+    pub fn is_violation(&self, trigger: f32) -> bool {
+        self.score >= trigger
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    bbox: BoundingBox,
+    class_id: usize,
+    confidence: f32,
+}
+
+/// 检测模式与执法模式共用的后处理
+///
+/// `output` 每行为 `[xc, yc, w, h, class_0, class_1, ...]`，坐标基于模型输入尺寸。
+pub(crate) fn postprocess(
+    output: ArrayView2<f32>,
+    img_width: u32,
+    img_height: u32,
+    frame_index: usize,
+    options: &PostprocessOptions,
+    labels: &[String],
+    label_kind: impl Fn(usize) -> LabelKind,
+) -> Vec<Detection> {
+    let scale_x = img_width as f32 / INPUT_SIZE as f32;
+    let scale_y = img_height as f32 / INPUT_SIZE as f32;
+    let img_area = img_width as f32 * img_height as f32;
+
+    let mut candidates = Vec::new();
+    for row in output.axis_iter(Axis(0)) {
+        let Some((class_id, confidence)) = row
+            .iter()
+            .skip(4)
+            .copied()
+            .enumerate()
+            .reduce(|accum, item| if item.1 > accum.1 { item } else { accum })
+        else {
+            continue;
+        };
+
+        if confidence < options.conf_threshold || label_kind(class_id) == LabelKind::Ignored {
+            continue;
+        }
+
+        let xc = row[0] * scale_x;
+        let yc = row[1] * scale_y;
+        let w = row[2] * scale_x;
+        let h = row[3] * scale_y;
+
+        if let Some(min_box_area) = options.min_box_area {
+            if w * h < min_box_area * img_area {
+                continue;
+            }
+        }
+        candidates.push(Candidate {
+            bbox: BoundingBox {
+                x1: xc - w / 2.,
+                y1: yc - h / 2.,
+                x2: xc + w / 2.,
+                y2: yc + h / 2.,
+            },
+            class_id,
+            confidence,
+        });
+    }
+
+    // 负类别同样参与 NMS，置信度更高的负类框会压制与之重叠的违规框
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    let mut kept: Vec<Candidate> = Vec::new();
+    for candidate in candidates {
+        if kept.len() >= options.max_detections {
+            break;
+        }
+
+        let suppressed = kept.iter().any(|k| {
+            (options.class_agnostic_nms || k.class_id == candidate.class_id)
+                && iou(&k.bbox, &candidate.bbox) >= options.nms_iou_threshold
+        });
+        if !suppressed {
+            kept.push(candidate);
+        }
+    }
+
+    kept.into_iter()
+        .filter(|c| label_kind(c.class_id) == LabelKind::Violation)
+        .map(|c| Detection {
+            label: labels.get(c.class_id).cloned().unwrap_or_default(),
+            confidence: c.confidence,
+            bbox: c.bbox,
+            frame_index,
+        })
+        .collect()
+}
+
+/// 图片的相似度，即违规框的最高置信度
+pub(crate) fn score(detections: &[Detection]) -> f32 {
+    detections.iter().map(|d| d.confidence).fold(0.0, f32::max)
+}
+
+pub(crate) fn intersection(box1: &BoundingBox, box2: &BoundingBox) -> f32 {
+    let w = box1.x2.min(box2.x2) - box1.x1.max(box2.x1);
+    let h = box1.y2.min(box2.y2) - box1.y1.max(box2.y1);
+    w.max(0.) * h.max(0.)
+}
+
+pub(crate) fn union(box1: &BoundingBox, box2: &BoundingBox) -> f32 {
+    ((box1.x2 - box1.x1) * (box1.y2 - box1.y1)) + ((box2.x2 - box2.x1) * (box2.y2 - box2.y1))
+        - intersection(box1, box2)
+}
+
+pub(crate) fn iou(box1: &BoundingBox, box2: &BoundingBox) -> f32 {
+    let union = union(box1, box2);
+    if union <= 0. {
+        return 0.;
+    }
+    intersection(box1, box2) / union
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr2, Array2};
+
+    fn labels() -> Vec<String> {
+        ["long", "other", "not_long"].map(String::from).to_vec()
+    }
+
+    fn label_kind(class_id: usize) -> LabelKind {
+        match class_id {
+            0 => LabelKind::Violation,
+            2 => LabelKind::Negative,
+            _ => LabelKind::Ignored,
+        }
+    }
+
+    /// 每行为 `[xc, yc, w, h, long, other, not_long]`，图片与模型输入同尺寸
+    fn output() -> Array2<f32> {
+        arr2(&[
+            // 违规框
+            [100., 100., 100., 100., 0.9, 0., 0.],
+            // 与上一个框重叠，置信度更低
+            [105., 100., 100., 100., 0.8, 0., 0.],
+            // 违规框，但被置信度更高的负类框覆盖
+            [400., 400., 100., 100., 0.95, 0., 0.],
+            [402., 400., 100., 100., 0., 0., 0.99],
+            // 低于 conf_threshold
+            [300., 100., 50., 50., 0.2, 0., 0.],
+            // 忽略类别
+            [500., 100., 50., 50., 0., 0.99, 0.],
+        ])
+    }
+
+    fn detection(confidence: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> Detection {
+        Detection {
+            label: "long".to_string(),
+            confidence,
+            bbox: BoundingBox { x1, y1, x2, y2 },
+            frame_index: 0,
+        }
+    }
+
+    fn run(class_agnostic_nms: bool) -> Vec<Detection> {
+        let options = PostprocessOptions {
+            class_agnostic_nms,
+            ..PostprocessOptions::default()
+        };
+        postprocess(
+            output().view(),
+            INPUT_SIZE,
+            INPUT_SIZE,
+            0,
+            &options,
+            &labels(),
+            label_kind,
+        )
+    }
+
+    #[test]
+    fn negative_box_suppresses_violation_when_class_agnostic() {
+        let detections = run(true);
+        assert_eq!(detections, vec![detection(0.9, 50., 50., 150., 150.)]);
+        assert_eq!(score(&detections), 0.9);
+    }
+
+    #[test]
+    fn class_aware_nms_keeps_violation_under_negative_box() {
+        let detections = run(false);
+        assert_eq!(
+            detections,
+            vec![
+                detection(0.95, 350., 350., 450., 450.),
+                detection(0.9, 50., 50., 150., 150.),
+            ]
+        );
+        assert_eq!(score(&detections), 0.95);
+    }
+
+    #[test]
+    fn score_without_detections_is_zero() {
+        assert_eq!(score(&[]), 0.);
+    }
+
+    #[test]
+    fn iou_of_boxes() {
+        let a = BoundingBox {
+            x1: 0.,
+            y1: 0.,
+            x2: 2.,
+            y2: 2.,
+        };
+        let b = BoundingBox {
+            x1: 1.,
+            y1: 0.,
+            x2: 3.,
+            y2: 2.,
+        };
+        let c = BoundingBox {
+            x1: 5.,
+            y1: 5.,
+            x2: 6.,
+            y2: 6.,
+        };
+        assert_eq!(iou(&a, &a), 1.);
+        assert_eq!(iou(&a, &b), 1. / 3.);
+        assert_eq!(iou(&a, &c), 0.);
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::template::Template;
+
+/// 限流与防刷屏配置，保存在 `rate_limit_config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct RateLimitOptions {
+    /// 每个成员在时间窗口内最多检测的图片数，0 表示不限制
+    pub(crate) user_max_images: usize,
+    pub(crate) user_window_secs: u64,
+    /// 每个群在时间窗口内最多检测的图片数，0 表示不限制
+    pub(crate) group_max_images: usize,
+    pub(crate) group_window_secs: u64,
+    /// 同一成员在该时间（秒）内再次违规时不再回复，只撤回
+    pub(crate) reply_interval_secs: u64,
+    /// 时间窗口内违规达到该次数时直接禁言，不受 `ban_cooldown` 限制，0 表示关闭
+    pub(crate) raid_detections: usize,
+    pub(crate) raid_window_secs: u64,
+    /// 刷屏禁言时长（秒）
+    pub(crate) raid_ban_duration: usize,
+    pub(crate) raid_msg: Template,
+}
+
+impl Default for RateLimitOptions {
+    fn default() -> Self {
+        Self {
+            user_max_images: 10,
+            user_window_secs: 60,
+            group_max_images: 40,
+            group_window_secs: 60,
+            reply_interval_secs: 10,
+            raid_detections: 5,
+            raid_window_secs: 60,
+            raid_ban_duration: 600,
+            raid_msg: "检测到刷屏，禁言处理👊".into(),
+        }
+    }
+}
+
+/// 滑动窗口内的计数
+#[derive(Default)]
+struct Window {
+    events: VecDeque<(Instant, usize)>,
+}
+
+impl Window {
+    fn count(&mut self, now: Instant, window: Duration) -> usize {
+        while let Some((time, _)) = self.events.front() {
+            if now.duration_since(*time) < window {
+                break;
+            }
+            self.events.pop_front();
+        }
+        self.events.iter().map(|(_, n)| n).sum()
+    }
+
+    fn push(&mut self, now: Instant, n: usize) {
+        if n > 0 {
+            self.events.push_back((now, n));
+        }
+    }
+
+    /// 从最近的记录中扣除
+    fn refund(&mut self, mut n: usize) {
+        while n > 0 {
+            let Some((_, last)) = self.events.back_mut() else {
+                break;
+            };
+            let k = n.min(*last);
+            *last -= k;
+            n -= k;
+            if *last == 0 {
+                self.events.pop_back();
+            }
+        }
+    }
+}
+
+/// 清理过期计数的间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct State {
+    users: HashMap<(i64, i64), Window>,
+    groups: HashMap<i64, Window>,
+    /// 键为检测器名、群号、QQ号
+    replies: HashMap<(String, i64, i64), Instant>,
+    detections: HashMap<(String, i64, i64), Window>,
+    last_sweep: Instant,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            users: HashMap::new(),
+            groups: HashMap::new(),
+            replies: HashMap::new(),
+            detections: HashMap::new(),
+            last_sweep: Instant::now(),
+        }
+    }
+
+    /// 定期移除已经过期的计数，避免长时间运行后占用的内存不断增长
+    fn sweep(&mut self, now: Instant, options: &RateLimitOptions) {
+        if now.duration_since(self.last_sweep) < SWEEP_INTERVAL {
+            return;
+        }
+        self.last_sweep = now;
+
+        let user_window = Duration::from_secs(options.user_window_secs);
+        let group_window = Duration::from_secs(options.group_window_secs);
+        let raid_window = Duration::from_secs(options.raid_window_secs);
+        let reply_interval = Duration::from_secs(options.reply_interval_secs);
+        self.users.retain(|_, v| v.count(now, user_window) > 0);
+        self.groups.retain(|_, v| v.count(now, group_window) > 0);
+        self.detections.retain(|_, v| v.count(now, raid_window) > 0);
+        self.replies
+            .retain(|_, last| now.duration_since(*last) < reply_interval);
+    }
+}
+
+/// 限制图片检测频率，合并短时间内的回复，并在刷屏时禁言
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    options: Arc<RateLimitOptions>,
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(options: RateLimitOptions) -> Self {
+        Self {
+            options: Arc::new(options),
+            state: Arc::new(Mutex::new(State::new())),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &RateLimitOptions {
+        &self.options
+    }
+
+    /// 预留至多 `max` 张图片的额度并立即计数，返回预留的张数
+    ///
+    /// 在下载前预留，同一成员同时发送的多条消息不会都通过检查；实际检测的张数较少时用 [`RateLimiter::refund`] 退回。
+    pub(crate) fn try_reserve(&self, group_id: i64, user_id: i64, max: usize) -> usize {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let options = &self.options;
+        state.sweep(now, options);
+
+        let mut allowance = max;
+        if options.user_max_images > 0 {
+            let used = state.users.get_mut(&(group_id, user_id)).map_or(0, |v| {
+                v.count(now, Duration::from_secs(options.user_window_secs))
+            });
+            allowance = allowance.min(options.user_max_images.saturating_sub(used));
+        }
+        if options.group_max_images > 0 {
+            let used = state.groups.get_mut(&group_id).map_or(0, |v| {
+                v.count(now, Duration::from_secs(options.group_window_secs))
+            });
+            allowance = allowance.min(options.group_max_images.saturating_sub(used));
+        }
+
+        if allowance > 0 {
+            state
+                .users
+                .entry((group_id, user_id))
+                .or_default()
+                .push(now, allowance);
+            state
+                .groups
+                .entry(group_id)
+                .or_default()
+                .push(now, allowance);
+        }
+        allowance
+    }
+
+    /// 退回预留后没有用到的额度
+    pub(crate) fn refund(&self, group_id: i64, user_id: i64, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some(window) = state.users.get_mut(&(group_id, user_id)) {
+            window.refund(n);
+        }
+        if let Some(window) = state.groups.get_mut(&group_id) {
+            window.refund(n);
+        }
+    }
+
+    /// 是否需要回复，`reply_interval_secs` 内的再次违规合并到上一次回复中
+    pub(crate) fn should_reply(&self, name: &str, group_id: i64, user_id: i64) -> bool {
+        let now = Instant::now();
+        let interval = Duration::from_secs(self.options.reply_interval_secs);
+        let mut state = self.state.lock().unwrap();
+        let key = (name.to_string(), group_id, user_id);
+        match state.replies.get(&key) {
+            Some(last) if now.duration_since(*last) < interval => false,
+            _ => {
+                state.replies.insert(key, now);
+                true
+            }
+        }
+    }
+
+    /// 记录一次违规，返回是否达到刷屏禁言的次数
+    pub(crate) fn record_detection(&self, name: &str, group_id: i64, user_id: i64) -> bool {
+        if self.options.raid_detections == 0 {
+            return false;
+        }
+        let now = Instant::now();
+        let window = Duration::from_secs(self.options.raid_window_secs);
+        let mut state = self.state.lock().unwrap();
+        let detections = state
+            .detections
+            .entry((name.to_string(), group_id, user_id))
+            .or_default();
+        detections.push(now, 1);
+        if detections.count(now, window) < self.options.raid_detections {
+            return false;
+        }
+        // 禁言后重新计数
+        detections.events.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitOptions {
+            user_max_images: 10,
+            group_max_images: 15,
+            ..RateLimitOptions::default()
+        })
+    }
+
+    #[test]
+    fn reservations_count_before_download() {
+        let limiter = limiter();
+        assert_eq!(limiter.try_reserve(1, 100, 20), 10);
+        // 前一条消息还在下载时，同一成员的后续消息没有额度
+        assert_eq!(limiter.try_reserve(1, 100, 20), 0);
+        // 群内剩余额度由其他成员共享
+        assert_eq!(limiter.try_reserve(1, 200, 20), 5);
+        assert_eq!(limiter.try_reserve(1, 300, 20), 0);
+    }
+
+    #[test]
+    fn refund_returns_unused_images() {
+        let limiter = limiter();
+        assert_eq!(limiter.try_reserve(1, 100, 20), 10);
+        limiter.refund(1, 100, 8);
+        assert_eq!(limiter.try_reserve(1, 100, 3), 3);
+        assert_eq!(limiter.try_reserve(1, 100, 20), 5);
+        assert_eq!(limiter.try_reserve(1, 200, 20), 5);
+    }
+}
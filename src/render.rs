@@ -0,0 +1,419 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops;
+use image::{DynamicImage, ExtendedColorType, ImageFormat, Rgba, RgbaImage};
+use raqote::{DrawOptions, DrawTarget, LineJoin, PathBuilder, SolidSource, Source, StrokeStyle};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::engine::{decode_frames, DetectError};
+use crate::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::postprocess::{BoundingBox, Detection, DetectionReport};
+
+/// 每个类别的框颜色，按类别名选取
+const PALETTE: [[u8; 3]; 6] = [
+    [255, 0, 0],
+    [0, 170, 255],
+    [255, 170, 0],
+    [170, 0, 255],
+    [0, 200, 80],
+    [255, 0, 170],
+];
+
+/// 检测区域的打码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Redaction {
+    None,
+    Mosaic,
+    Blur,
+}
+
+/// 标注图片的编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    /// 无损 WebP
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+        }
+    }
+}
+
+/// 标注图片的绘制参数，平铺在检测器配置中
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderOptions {
+    /// 是否在框上标注类别与置信度
+    pub captions: bool,
+    /// 检测区域的打码方式
+    pub redaction: Redaction,
+    pub output_format: OutputFormat,
+    /// JPEG 质量，1-100
+    pub jpeg_quality: u8,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            captions: true,
+            redaction: Redaction::None,
+            output_format: OutputFormat::Jpeg,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+/// 按检测报告重新解码图片并绘制检测框，与检测模式回复的图片一致
+pub fn render_report(
+    img_data: &[u8],
+    report: &DetectionReport,
+    options: &RenderOptions,
+) -> Result<RgbaImage, DetectError> {
+    let frames = decode_frames(img_data, report.frames)?;
+    Ok(draw_report(report, &frames, options))
+}
+
+/// 在置信度最高的违规框所在帧上绘制检测框
+pub(crate) fn draw_report(
+    report: &DetectionReport,
+    frames: &[DynamicImage],
+    options: &RenderOptions,
+) -> RgbaImage {
+    let (frame_index, detections) = best_frame(report);
+    draw_detections(&frames[frame_index], &detections, options)
+}
+
+/// 只对违规区域打码、不绘制检测框，用于重发消息；GIF 只保留置信度最高的一帧
+pub(crate) fn redact_report(
+    report: &DetectionReport,
+    frames: &[DynamicImage],
+    redaction: Redaction,
+) -> RgbaImage {
+    let (frame_index, detections) = best_frame(report);
+    let mut img = frames[frame_index].to_rgba8();
+    for detection in &detections {
+        redact(&mut img, &detection.bbox, redaction);
+    }
+    img
+}
+
+/// 置信度最高的违规框所在帧及该帧的所有违规框
+pub(crate) fn best_frame(report: &DetectionReport) -> (usize, Vec<Detection>) {
+    let frame_index = report
+        .detections
+        .iter()
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+        .map(|d| d.frame_index)
+        .unwrap_or(0);
+    let detections = report
+        .detections
+        .iter()
+        .filter(|d| d.frame_index == frame_index)
+        .cloned()
+        .collect();
+    (frame_index, detections)
+}
+
+/// 在图片上绘制检测框
+pub fn draw_detections(
+    original_img: &DynamicImage,
+    detections: &[Detection],
+    options: &RenderOptions,
+) -> RgbaImage {
+    let (img_width, img_height) = (original_img.width(), original_img.height());
+
+    let mut base = original_img.to_rgba8();
+    if options.redaction != Redaction::None {
+        for detection in detections {
+            redact(&mut base, &detection.bbox, options.redaction);
+        }
+    }
+
+    let mut dt = DrawTarget::new(img_width as _, img_height as _);
+
+    for detection in detections {
+        let bbox = detection.bbox;
+        let mut pb = PathBuilder::new();
+        pb.rect(bbox.x1, bbox.y1, bbox.x2 - bbox.x1, bbox.y2 - bbox.y1);
+        let path = pb.finish();
+
+        let [r, g, b] = label_color(&detection.label);
+        let color = SolidSource { r, g, b, a: 255 };
+
+        dt.stroke(
+            &path,
+            &Source::Solid(color),
+            &StrokeStyle {
+                join: LineJoin::Round,
+                width: 4.,
+                ..StrokeStyle::default()
+            },
+            &DrawOptions::new(),
+        );
+    }
+
+    let box_img: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> = image::RgbaImage::from_raw(
+        img_width,
+        img_height,
+        dt.get_data()
+            .iter()
+            .flat_map(|&p| {
+                let a = (p >> 24) & 0xff;
+                let r = (p >> 16) & 0xff;
+                let g = (p >> 8) & 0xff;
+                let b = p & 0xff;
+                vec![r as u8, g as u8, b as u8, a as u8]
+            })
+            .collect(),
+    )
+    .unwrap();
+
+    let mut res_img = image::RgbaImage::new(img_width, img_height);
+    for (x, y, pixel) in res_img.enumerate_pixels_mut() {
+        let original_pixel = base.get_pixel(x, y);
+        let box_pixel = box_img.get_pixel(x, y);
+
+        if box_pixel.0[3] > 0 {
+            *pixel = image::Rgba([
+                box_pixel.0[0],
+                box_pixel.0[1],
+                box_pixel.0[2],
+                box_pixel.0[3],
+            ]);
+        } else {
+            *pixel = image::Rgba([
+                original_pixel.0[0],
+                original_pixel.0[1],
+                original_pixel.0[2],
+                255,
+            ]);
+        }
+    }
+
+    if options.captions {
+        for detection in detections {
+            let text = format!("{} {:.2}", detection.label, detection.confidence);
+            draw_caption(
+                &mut res_img,
+                &text,
+                label_color(&detection.label),
+                &detection.bbox,
+            );
+        }
+    }
+
+    res_img
+}
+
+/// 按配置的格式编码标注图片
+pub fn encode_image(img: &RgbaImage, options: &RenderOptions) -> image::ImageResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    match options.output_format {
+        OutputFormat::Png => img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?,
+        OutputFormat::Jpeg => {
+            let rgb = DynamicImage::ImageRgba8(img.clone()).to_rgb8();
+            JpegEncoder::new_with_quality(&mut buf, options.jpeg_quality.clamp(1, 100))
+                .encode_image(&rgb)?;
+        }
+        OutputFormat::Webp => WebPEncoder::new_lossless(&mut buf).encode(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            ExtendedColorType::Rgba8,
+        )?,
+    }
+    Ok(buf)
+}
+
+fn label_color(label: &str) -> [u8; 3] {
+    // FNV-1a，保证同一类别在不同图片中颜色一致
+    let hash = label.bytes().fold(0x811c9dc5u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x01000193)
+    });
+    PALETTE[hash as usize % PALETTE.len()]
+}
+
+/// 把框裁剪到图片范围内，返回 `(x, y, w, h)`
+fn clip(bbox: &BoundingBox, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let x1 = bbox.x1.max(0.) as u32;
+    let y1 = bbox.y1.max(0.) as u32;
+    let x2 = (bbox.x2.max(0.) as u32).min(width);
+    let y2 = (bbox.y2.max(0.) as u32).min(height);
+    if x2 <= x1 || y2 <= y1 {
+        return None;
+    }
+    Some((x1, y1, x2 - x1, y2 - y1))
+}
+
+/// 对框内区域打码
+pub(crate) fn redact(img: &mut RgbaImage, bbox: &BoundingBox, redaction: Redaction) {
+    let Some((x, y, w, h)) = clip(bbox, img.width(), img.height()) else {
+        return;
+    };
+
+    match redaction {
+        Redaction::None => {}
+        Redaction::Mosaic => {
+            let block = (w.min(h) / 12).max(4);
+            for by in (y..y + h).step_by(block as usize) {
+                for bx in (x..x + w).step_by(block as usize) {
+                    let bw = block.min(x + w - bx);
+                    let bh = block.min(y + h - by);
+
+                    let mut sum = [0u64; 4];
+                    for py in by..by + bh {
+                        for px in bx..bx + bw {
+                            for (s, v) in sum.iter_mut().zip(img.get_pixel(px, py).0) {
+                                *s += v as u64;
+                            }
+                        }
+                    }
+                    let n = (bw * bh) as u64;
+                    let avg = Rgba(sum.map(|s| (s / n) as u8));
+
+                    for py in by..by + bh {
+                        for px in bx..bx + bw {
+                            img.put_pixel(px, py, avg);
+                        }
+                    }
+                }
+            }
+        }
+        Redaction::Blur => {
+            let region = imageops::crop_imm(img, x, y, w, h).to_image();
+            let sigma = (w.min(h) as f32 / 10.).max(4.);
+            let blurred = imageops::blur(&region, sigma);
+            imageops::replace(img, &blurred, x as i64, y as i64);
+        }
+    }
+}
+
+/// 在框的左上角绘制带背景的标注文字，框上方放不下时画在框内
+fn draw_caption(img: &mut RgbaImage, text: &str, color: [u8; 3], bbox: &BoundingBox) {
+    let (width, height) = (img.width(), img.height());
+    let scale = (width.min(height) / 240).max(1);
+    let padding = 2 * scale;
+    let advance = (GLYPH_WIDTH + 1) * scale;
+
+    let chars = text.chars().count() as u32;
+    let caption_w = (chars * advance).saturating_sub(scale) + 2 * padding;
+    let caption_h = GLYPH_HEIGHT * scale + 2 * padding;
+
+    let x = (bbox.x1.max(0.) as u32).min(width.saturating_sub(caption_w));
+    let top = bbox.y1.max(0.) as u32;
+    let y = if top >= caption_h {
+        top - caption_h
+    } else {
+        top
+    };
+
+    let background = Rgba([color[0], color[1], color[2], 255]);
+    fill(img, x, y, caption_w, caption_h, background);
+
+    // 按背景亮度选择黑字或白字
+    let luma = 299 * color[0] as u32 + 587 * color[1] as u32 + 114 * color[2] as u32;
+    let foreground = if luma > 150_000 {
+        Rgba([0, 0, 0, 255])
+    } else {
+        Rgba([255, 255, 255, 255])
+    };
+
+    for (i, c) in text.chars().enumerate() {
+        let gx = x + padding + i as u32 * advance;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    let px = gx + col * scale;
+                    let py = y + padding + row as u32 * scale;
+                    fill(img, px, py, scale, scale, foreground);
+                }
+            }
+        }
+    }
+}
+
+fn fill(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>) {
+    for py in y..(y + h).min(img.height()) {
+        for px in x..(x + w).min(img.width()) {
+            img.put_pixel(px, py, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(confidence: f32, frame_index: usize, x1: f32, y1: f32) -> Detection {
+        Detection {
+            label: "long".to_string(),
+            confidence,
+            bbox: BoundingBox {
+                x1,
+                y1,
+                x2: x1 + 100.,
+                y2: y1 + 100.,
+            },
+            frame_index,
+        }
+    }
+
+    /// 第 1 帧的违规框置信度最高
+    fn report() -> DetectionReport {
+        DetectionReport {
+            detector: "龙图".to_string(),
+            score: 0.9,
+            detections: vec![
+                detection(0.6, 0, 50., 50.),
+                detection(0.9, 1, 350., 350.),
+                detection(0.5, 1, 50., 50.),
+            ],
+            frames: 2,
+            width: 640,
+            height: 640,
+        }
+    }
+
+    fn frames() -> [DynamicImage; 2] {
+        let frame = |color| DynamicImage::ImageRgba8(RgbaImage::from_pixel(640, 640, Rgba(color)));
+        [frame([255, 0, 0, 255]), frame([0, 0, 255, 255])]
+    }
+
+    #[test]
+    fn draw_report_uses_best_frame() {
+        let options = RenderOptions {
+            captions: false,
+            ..RenderOptions::default()
+        };
+        let img = draw_report(&report(), &frames(), &options);
+        assert_eq!(img.get_pixel(600, 20), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn best_frame_keeps_boxes_of_that_frame() {
+        let report = report();
+        let (frame_index, detections) = best_frame(&report);
+        assert_eq!(frame_index, 1);
+        assert_eq!(
+            detections,
+            vec![detection(0.9, 1, 350., 350.), detection(0.5, 1, 50., 50.)]
+        );
+    }
+
+    #[test]
+    fn redact_report_uses_best_frame() {
+        let img = redact_report(&report(), &frames(), Redaction::Mosaic);
+        assert_eq!(img.get_pixel(600, 20), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(400, 400), &Rgba([0, 0, 255, 255]));
+    }
+}
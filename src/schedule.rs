@@ -0,0 +1,125 @@
+use kovi::chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveTime, Utc};
+use kovi::log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 一段戒严时间，`end` 不晚于 `start` 时跨越午夜
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Window {
+    /// 星期几，1 为周一，7 为周日，为空时每天生效；跨午夜时按开始的那天计算
+    #[serde(default)]
+    pub(crate) weekdays: Vec<u32>,
+    /// 开始时间，形如 `09:00`
+    pub(crate) start: String,
+    /// 结束时间，形如 `18:00`
+    pub(crate) end: String,
+}
+
+impl Window {
+    fn contains(&self, now: DateTime<FixedOffset>) -> bool {
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            error!("无法解析戒严时间: {} - {}", self.start, self.end);
+            return false;
+        };
+        let time = now.time();
+        let today = now.weekday().number_from_monday();
+        let yesterday = (now - Duration::days(1)).weekday().number_from_monday();
+
+        if start < end {
+            self.on(today) && start <= time && time < end
+        } else {
+            (self.on(today) && start <= time) || (self.on(yesterday) && time < end)
+        }
+    }
+
+    fn on(&self, weekday: u32) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&weekday)
+    }
+}
+
+/// 按时间自动开启、关闭戒严，对应检测器配置中的 `schedule`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ScheduleOptions {
+    /// 时区，相对 UTC 的分钟数，默认东八区
+    pub(crate) utc_offset_minutes: i32,
+    /// 检查间隔（秒）
+    pub(crate) check_interval_secs: u64,
+    /// 切换时是否在群里发送开启、关闭提示
+    pub(crate) is_announce: bool,
+    /// 各群的戒严时间，键为群号
+    pub(crate) groups: HashMap<i64, Vec<Window>>,
+}
+
+impl Default for ScheduleOptions {
+    fn default() -> Self {
+        Self {
+            utc_offset_minutes: 8 * 60,
+            check_interval_secs: 30,
+            is_announce: true,
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl ScheduleOptions {
+    /// 各群当前是否处于戒严时间
+    pub(crate) fn active_groups(&self) -> HashMap<i64, bool> {
+        let offset = FixedOffset::east_opt(self.utc_offset_minutes * 60).unwrap_or_else(|| {
+            error!("无效的时区: {} 分钟，使用 UTC", self.utc_offset_minutes);
+            FixedOffset::east_opt(0).unwrap()
+        });
+        let now = Utc::now().with_timezone(&offset);
+
+        self.groups
+            .iter()
+            .map(|(group_id, windows)| (*group_id, windows.iter().any(|v| v.contains(now))))
+            .collect()
+    }
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kovi::chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<FixedOffset> {
+        // 2024-01-05 为周五
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn window(weekdays: Vec<u32>, start: &str, end: &str) -> Window {
+        Window {
+            weekdays,
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    #[test]
+    fn window_within_day() {
+        let window = window(vec![], "09:00", "18:00");
+        assert!(!window.contains(at(5, 8, 59)));
+        assert!(window.contains(at(5, 9, 0)));
+        assert!(window.contains(at(5, 17, 59)));
+        assert!(!window.contains(at(5, 18, 0)));
+    }
+
+    #[test]
+    fn window_across_midnight_counts_from_start_day() {
+        let window = window(vec![5], "22:00", "02:00");
+        assert!(window.contains(at(5, 23, 0)));
+        assert!(window.contains(at(6, 1, 59)));
+        assert!(!window.contains(at(6, 2, 0)));
+        // 周四晚上不生效，所以周五凌晨也不生效
+        assert!(!window.contains(at(5, 1, 0)));
+        assert!(!window.contains(at(6, 23, 0)));
+    }
+}
@@ -0,0 +1,182 @@
+use kovi::{AllMsgEvent, Message};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::hash::BuildHasher;
+
+/// 消息模板，写成数组时每次随机选一条
+///
+/// `{name}` 替换为同名变量，未提供的变量原样保留；`{at}` 替换为 @ 发送者。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Template {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<&str> for Template {
+    fn from(v: &str) -> Self {
+        Template::One(v.to_string())
+    }
+}
+
+impl Template {
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            Template::One(v) => v.is_empty(),
+            Template::Many(v) => v.iter().all(String::is_empty),
+        }
+    }
+
+    fn pick(&self) -> &str {
+        match self {
+            Template::One(v) => v,
+            Template::Many(v) if v.is_empty() => "",
+            Template::Many(v) => {
+                // 每个 RandomState 的种子都不同，足够用来挑选文案
+                let i = RandomState::new().hash_one(v.len()) as usize % v.len();
+                &v[i]
+            }
+        }
+    }
+
+    /// 渲染为文本，`{at}` 替换为 `@昵称`
+    pub(crate) fn render(&self, vars: &Vars) -> String {
+        let mut text = String::new();
+        for piece in vars.expand(self.pick()) {
+            match piece {
+                Piece::Text(v) => text.push_str(&v),
+                Piece::At => {
+                    if let Some((_, nickname)) = &vars.at {
+                        text.push('@');
+                        text.push_str(nickname);
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    /// 渲染为消息，`{at}` 替换为 @ 消息段
+    pub(crate) fn message(&self, vars: &Vars) -> Message {
+        let mut msg = Message::new();
+        for piece in vars.expand(self.pick()) {
+            match piece {
+                Piece::Text(v) if v.is_empty() => {}
+                Piece::Text(v) => msg.push_text(v),
+                Piece::At => {
+                    if let Some((user_id, _)) = &vars.at {
+                        msg.push_at(&user_id.to_string());
+                    }
+                }
+            }
+        }
+        msg
+    }
+}
+
+/// 展开后的模板片段
+enum Piece {
+    Text(String),
+    At,
+}
+
+/// 模板变量
+#[derive(Default)]
+pub(crate) struct Vars {
+    values: Vec<(&'static str, String)>,
+    /// 被 @ 的 QQ 号与昵称
+    at: Option<(i64, String)>,
+}
+
+impl Vars {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn set(mut self, key: &'static str, value: impl Display) -> Self {
+        self.values.push((key, value.to_string()));
+        self
+    }
+
+    /// 消息发送者：`{user}`、`{nickname}`、`{user_id}` 与 `{at}`
+    pub(crate) fn sender(self, e: &AllMsgEvent) -> Self {
+        let nickname = e.sender.nickname.clone().unwrap_or_default();
+        let mut vars = self
+            .set("user", &nickname)
+            .set("nickname", &nickname)
+            .set("user_id", e.user_id);
+        vars.at = Some((e.user_id, nickname));
+        vars
+    }
+
+    /// 禁言时长：`{ban_seconds}` 与向上取整的 `{ban_minutes}`
+    pub(crate) fn ban(self, duration: usize) -> Self {
+        self.set("ban_seconds", duration)
+            .set("ban_minutes", duration.div_ceil(60))
+    }
+
+    /// 只扫描一遍模板，变量的值原样插入，不会再被当作占位符展开
+    fn expand(&self, template: &str) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let Some(end) = after.find('}') else {
+                rest = &rest[start..];
+                break;
+            };
+            let key = &after[..end];
+            if key == "at" {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+                pieces.push(Piece::At);
+            } else {
+                match self.values.iter().find(|(k, _)| *k == key) {
+                    Some((_, value)) => text.push_str(value),
+                    None => {
+                        text.push('{');
+                        text.push_str(key);
+                        text.push('}');
+                    }
+                }
+            }
+            rest = &after[end + 1..];
+        }
+        text.push_str(rest);
+        pieces.push(Piece::Text(text));
+        pieces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_replaces_known_vars_only() {
+        let vars = Vars::new().set("detector", "龙图").set("prob", "0.90");
+        assert_eq!(
+            Template::from("{detector} {prob} {unknown} {").render(&vars),
+            "龙图 0.90 {unknown} {"
+        );
+    }
+
+    #[test]
+    fn render_does_not_expand_values() {
+        let mut vars = Vars::new()
+            .set("nickname", "{prob}{at}")
+            .set("prob", "0.90");
+        vars.at = Some((10000, "{prob}".to_string()));
+        assert_eq!(
+            Template::Many(vec!["{at} {nickname} {prob}".to_string()]).render(&vars),
+            "@{prob} {prob}{at} 0.90"
+        );
+    }
+
+    #[test]
+    fn render_at_without_sender_is_empty() {
+        assert_eq!(Template::from("{at}hi").render(&Vars::new()), "hi");
+    }
+}
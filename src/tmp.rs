@@ -0,0 +1,231 @@
+use kovi::log::{error, info};
+use kovi::tokio;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// 临时文件配置，保存在 `tmp_config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TmpOptions {
+    /// 发送后至少保留的时间（秒），留给 OneBot 实现读取文件
+    pub(crate) hold_secs: u64,
+    /// 超过该时间（秒）且不再被引用的文件由清理任务删除
+    pub(crate) ttl_secs: u64,
+    /// 清理任务的运行间隔（秒）
+    pub(crate) janitor_interval_secs: u64,
+    /// 目录大小上限（字节），超出时从最旧的未引用文件开始删除
+    pub(crate) max_dir_bytes: u64,
+}
+
+impl Default for TmpOptions {
+    fn default() -> Self {
+        Self {
+            hold_secs: 10,
+            ttl_secs: 120,
+            janitor_interval_secs: 60,
+            max_dir_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// 管理 `data/tmp` 下的输出文件，所有检测器共用一个实例
+#[derive(Clone)]
+pub(crate) struct TmpManager {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    options: TmpOptions,
+    counter: AtomicU64,
+    /// 仍被句柄引用的文件，清理任务不会删除
+    held: Mutex<HashSet<PathBuf>>,
+}
+
+/// 临时文件句柄，最后一个克隆被释放时删除文件
+#[derive(Clone)]
+pub(crate) struct TmpFile {
+    inner: Arc<TmpFileInner>,
+}
+
+struct TmpFileInner {
+    path: PathBuf,
+    manager: Arc<Inner>,
+}
+
+impl TmpManager {
+    pub(crate) fn new(dir: PathBuf, options: TmpOptions) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                dir,
+                options,
+                counter: AtomicU64::new(0),
+                held: Mutex::new(HashSet::new()),
+            }),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &TmpOptions {
+        &self.inner.options
+    }
+
+    /// 以唯一文件名写入数据，`name` 只用于便于辨认
+    pub(crate) async fn write(
+        &self,
+        name: &str,
+        extension: &str,
+        data: &[u8],
+    ) -> std::io::Result<TmpFile> {
+        tokio::fs::create_dir_all(&self.inner.dir).await?;
+
+        // 时间戳 + 进程号 + 自增序号，同一秒内多次渲染也不会重名
+        let filename = format!(
+            "{}-{}-{}-{}.{}",
+            kovi::chrono::Local::now().format("%Y-%m-%d-%H-%M-%S"),
+            std::process::id(),
+            self.inner.counter.fetch_add(1, Ordering::Relaxed),
+            name,
+            extension
+        );
+        let path = self.inner.dir.join(filename);
+
+        self.inner.held.lock().unwrap().insert(path.clone());
+        let file = TmpFile {
+            inner: Arc::new(TmpFileInner {
+                path,
+                manager: self.inner.clone(),
+            }),
+        };
+        tokio::fs::write(file.path(), data).await?;
+
+        self.enforce_size_cap().await;
+        Ok(file)
+    }
+
+    /// 启动后台清理任务
+    pub(crate) fn spawn_janitor(&self) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(manager.options().janitor_interval_secs.max(1));
+            loop {
+                tokio::time::sleep(interval).await;
+                manager.remove_expired().await;
+                manager.enforce_size_cap().await;
+            }
+        })
+    }
+
+    /// 删除目录中所有未被引用的文件，插件卸载时调用
+    pub(crate) async fn clear(&self) {
+        for (path, _, _) in self.unheld_files().await {
+            remove(&path).await;
+        }
+    }
+
+    async fn remove_expired(&self) {
+        let ttl = Duration::from_secs(self.options().ttl_secs);
+        let now = SystemTime::now();
+        for (path, modified, _) in self.unheld_files().await {
+            if now.duration_since(modified).unwrap_or_default() >= ttl {
+                remove(&path).await;
+            }
+        }
+    }
+
+    async fn enforce_size_cap(&self) {
+        let mut files = self.unheld_files().await;
+        let held_size = self.held_size().await;
+        let mut total: u64 = held_size + files.iter().map(|(_, _, len)| len).sum::<u64>();
+        if total <= self.options().max_dir_bytes {
+            return;
+        }
+
+        files.sort_by_key(|(_, modified, _)| *modified);
+        for (path, _, len) in files {
+            if total <= self.options().max_dir_bytes {
+                break;
+            }
+            remove(&path).await;
+            total = total.saturating_sub(len);
+        }
+        if total > self.options().max_dir_bytes {
+            info!("临时目录仍超出大小上限，剩余文件都在使用中");
+        }
+    }
+
+    async fn held_size(&self) -> u64 {
+        let held: Vec<_> = self.inner.held.lock().unwrap().iter().cloned().collect();
+        let mut size = 0;
+        for path in held {
+            if let Ok(metadata) = tokio::fs::metadata(&path).await {
+                size += metadata.len();
+            }
+        }
+        size
+    }
+
+    /// 目录中未被句柄引用的文件，返回路径、修改时间和大小
+    async fn unheld_files(&self) -> Vec<(PathBuf, SystemTime, u64)> {
+        let mut files = Vec::new();
+        let Ok(mut entries) = tokio::fs::read_dir(&self.inner.dir).await else {
+            return files;
+        };
+
+        while let Some(entry) = entries.next_entry().await.unwrap_or(None) {
+            let path = entry.path();
+            if self.inner.held.lock().unwrap().contains(&path) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((path, modified, metadata.len()));
+        }
+        files
+    }
+}
+
+impl TmpFile {
+    pub(crate) fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// 在 `hold_secs` 后释放句柄，不阻塞调用方
+    pub(crate) fn release_later(files: Vec<TmpFile>) {
+        let Some(first) = files.first() else {
+            return;
+        };
+        let hold = Duration::from_secs(first.inner.manager.options.hold_secs);
+        tokio::spawn(async move {
+            tokio::time::sleep(hold).await;
+            drop(files);
+        });
+    }
+}
+
+impl Drop for TmpFileInner {
+    fn drop(&mut self) {
+        self.manager.held.lock().unwrap().remove(&self.path);
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                error!("删除临时文件失败 {:?}: {}", self.path, err);
+            }
+        }
+    }
+}
+
+async fn remove(path: &Path) {
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            error!("删除临时文件失败 {:?}: {}", path, err);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use image::DynamicImage;
+use std::fmt;
+
+#[derive(Debug)]
+pub(crate) enum VideoError {
+    /// 编译时未启用 `video` 功能
+    #[cfg(not(feature = "video"))]
+    Disabled,
+    /// 没有 H.264 视频轨道
+    #[cfg(feature = "video")]
+    NoVideoTrack,
+    /// 关键帧像素数超过 `max_pixels`
+    #[cfg(feature = "video")]
+    TooManyPixels(usize, usize),
+    #[cfg(feature = "video")]
+    Mp4(mp4::Error),
+    #[cfg(feature = "video")]
+    Decode(openh264::Error),
+}
+
+impl fmt::Display for VideoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(not(feature = "video"))]
+            VideoError::Disabled => write!(f, "未启用 video 功能"),
+            #[cfg(feature = "video")]
+            VideoError::NoVideoTrack => write!(f, "视频中没有 H.264 轨道"),
+            #[cfg(feature = "video")]
+            VideoError::TooManyPixels(w, h) => write!(f, "视频尺寸过大: {}x{}", w, h),
+            #[cfg(feature = "video")]
+            VideoError::Mp4(err) => write!(f, "视频解析失败: {}", err),
+            #[cfg(feature = "video")]
+            VideoError::Decode(err) => write!(f, "视频解码失败: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for VideoError {}
+
+#[cfg(feature = "video")]
+impl From<mp4::Error> for VideoError {
+    fn from(err: mp4::Error) -> Self {
+        VideoError::Mp4(err)
+    }
+}
+
+#[cfg(feature = "video")]
+impl From<openh264::Error> for VideoError {
+    fn from(err: openh264::Error) -> Self {
+        VideoError::Decode(err)
+    }
+}
+
+/// 从 MP4 中均匀抽取至多 `max_frames` 个关键帧，单帧像素数不超过 `max_pixels`
+#[cfg(feature = "video")]
+pub(crate) fn keyframes(
+    data: &[u8],
+    max_frames: usize,
+    max_pixels: u64,
+) -> Result<Vec<DynamicImage>, VideoError> {
+    use mp4::{MediaType, Mp4Reader};
+    use openh264::decoder::Decoder;
+    use openh264::formats::YUVSource;
+    use std::io::Cursor;
+
+    let mut reader = Mp4Reader::read_header(Cursor::new(data), data.len() as u64)?;
+    let track = reader
+        .tracks()
+        .values()
+        .find(|track| matches!(track.media_type(), Ok(MediaType::H264)))
+        .ok_or(VideoError::NoVideoTrack)?;
+    let track_id = track.track_id();
+    let sample_count = track.sample_count();
+
+    // SPS 与 PPS 放在每个关键帧前，关键帧可以单独解码
+    let mut header = Vec::new();
+    for nal in [
+        track.sequence_parameter_set()?,
+        track.picture_parameter_set()?,
+    ] {
+        header.extend_from_slice(&[0, 0, 0, 1]);
+        header.extend_from_slice(nal);
+    }
+
+    let mut sync_samples = Vec::new();
+    for sample_id in 1..=sample_count {
+        if let Some(sample) = reader.read_sample(track_id, sample_id)? {
+            if sample.is_sync {
+                sync_samples.push(sample.bytes);
+            }
+        }
+    }
+    if sync_samples.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let wanted = max_frames.clamp(1, sync_samples.len());
+    let step = sync_samples.len() / wanted;
+
+    let mut decoder = Decoder::new()?;
+    let mut frames = Vec::new();
+    for sample in sync_samples.iter().step_by(step).take(wanted) {
+        let mut packet = header.clone();
+        packet.extend(avcc_to_annexb(sample));
+
+        let Some(yuv) = decoder.decode(&packet)? else {
+            continue;
+        };
+        let (width, height) = yuv.dimensions();
+        if (width * height) as u64 > max_pixels {
+            return Err(VideoError::TooManyPixels(width, height));
+        }
+        let mut rgb = vec![0; width * height * 3];
+        yuv.write_rgb8(&mut rgb);
+        if let Some(img) = image::RgbImage::from_raw(width as u32, height as u32, rgb) {
+            frames.push(DynamicImage::ImageRgb8(img));
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(not(feature = "video"))]
+pub(crate) fn keyframes(
+    _data: &[u8],
+    _max_frames: usize,
+    _max_pixels: u64,
+) -> Result<Vec<DynamicImage>, VideoError> {
+    Err(VideoError::Disabled)
+}
+
+/// MP4 中的 NAL 单元以 4 字节长度开头，解码器需要起始码
+#[cfg(feature = "video")]
+fn avcc_to_annexb(sample: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(sample.len());
+    let mut rest = sample;
+    while rest.len() > 4 {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let end = (4 + len).min(rest.len());
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(&rest[4..end]);
+        rest = &rest[end..];
+    }
+    out
+}